@@ -1,6 +1,13 @@
 use std::fmt::{Display, Formatter, Result};
 use tokio_serial::{DataBits, FlowControl, Parity, StopBits};
 
+use crate::encoding::Encoding;
+use crate::highlight::HighlightRule;
+use crate::keyboard_input::control_byte;
+use crate::line_ending::LineEnding;
+use crate::rotation::Interval;
+use crate::rx_display::ControlChars;
+
 #[derive(Debug, Clone)]
 pub struct CLIDisplay<T> {
     pub name: String,
@@ -106,3 +113,148 @@ pub fn valid_stop_bits(val: &str) -> std::result::Result<CLIDisplay<StopBits>, S
         )),
     }
 }
+
+pub fn valid_speed(val: &str) -> std::result::Result<f64, String> {
+    match val.to_lowercase().as_str() {
+        "max" => Ok(f64::INFINITY),
+        _ => match val.parse::<f64>() {
+            Ok(speed) if speed > 0.0 => Ok(speed),
+            _ => Err(format!(
+                "Invalid speed '{}' specified, expected a factor > 0 or 'max'",
+                val
+            )),
+        },
+    }
+}
+
+pub fn valid_size(val: &str) -> std::result::Result<u64, String> {
+    let (number, multiplier) = match val.to_uppercase().trim_end_matches('B') {
+        v if v.ends_with('K') => (v.trim_end_matches('K').to_string(), 1 << 10),
+        v if v.ends_with('M') => (v.trim_end_matches('M').to_string(), 1 << 20),
+        v if v.ends_with('G') => (v.trim_end_matches('G').to_string(), 1 << 30),
+        v => (v.to_string(), 1),
+    };
+    match number
+        .parse::<u64>()
+        .map(|size| size.checked_mul(multiplier))
+    {
+        Ok(Some(size)) if size > 0 => Ok(size),
+        _ => Err(format!(
+            "Invalid size '{}' specified, expected bytes or a number with K, M, or G",
+            val
+        )),
+    }
+}
+
+pub fn valid_chunk_size(val: &str) -> std::result::Result<usize, String> {
+    match val.parse::<usize>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!(
+            "Invalid chunk size '{}' specified, expected bytes > 0",
+            val
+        )),
+    }
+}
+
+pub fn valid_interval(val: &str) -> std::result::Result<Interval, String> {
+    match val.to_lowercase().as_str() {
+        "hourly" => Ok(Interval::Hourly),
+        "daily" => Ok(Interval::Daily),
+        _ => Err(format!(
+            "Invalid rotation '{}' specified, expected 'hourly' or 'daily'",
+            val
+        )),
+    }
+}
+
+pub fn valid_encoding(val: &str) -> std::result::Result<Encoding, String> {
+    match val.to_lowercase().as_str() {
+        "utf-8" | "utf8" => Ok(Encoding::Utf8),
+        "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+        "cp437" | "ibm437" => Ok(Encoding::Cp437),
+        "ascii" => Ok(Encoding::Ascii),
+        _ => Err(format!(
+            "Invalid encoding '{}' specified, expected 'utf-8', 'latin1', 'cp437', or 'ascii'",
+            val
+        )),
+    }
+}
+
+pub fn valid_rx_eol(val: &str) -> std::result::Result<LineEnding, String> {
+    match LineEnding::parse(val) {
+        Some(line_ending) => Ok(line_ending),
+        None => Err(format!(
+            "Invalid line ending '{}' specified, expected 'cr', 'lf', 'crlf', or 'auto'",
+            val
+        )),
+    }
+}
+
+pub fn valid_tx_eol(val: &str) -> std::result::Result<LineEnding, String> {
+    match LineEnding::parse(val) {
+        Some(line_ending) if line_ending != LineEnding::Auto => Ok(line_ending),
+        _ => Err(format!(
+            "Invalid line ending '{}' specified, expected 'cr', 'lf', or 'crlf'",
+            val
+        )),
+    }
+}
+
+pub fn valid_escape_key(val: &str) -> std::result::Result<CLIDisplay<u8>, String> {
+    let lowercase = val.to_lowercase();
+    let key = lowercase
+        .strip_prefix("ctrl-")
+        .or_else(|| lowercase.strip_prefix('^'));
+    let mut chars = key.unwrap_or_default().chars();
+    match (chars.next(), chars.next()) {
+        (Some(key), None) if control_byte(key).is_some() => Ok(CLIDisplay {
+            name: String::from(val),
+            value: control_byte(key).unwrap(),
+        }),
+        _ => Err(format!(
+            "Invalid escape key '{}' specified, expected e.g. 'ctrl-t', 'ctrl-]', or '^a'",
+            val
+        )),
+    }
+}
+
+pub fn valid_highlight(val: &str) -> std::result::Result<HighlightRule, String> {
+    HighlightRule::parse(val)
+        .map_err(|error| format!("Invalid highlight rule '{}' specified, {}", val, error))
+}
+
+pub fn valid_control_chars(val: &str) -> std::result::Result<ControlChars, String> {
+    match ControlChars::parse(val) {
+        Some(control_chars) => Ok(control_chars),
+        None => Err(format!(
+            "Invalid control characters '{}' specified, expected 'raw', 'caret', 'pictures', or 'hex'",
+            val
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_escape_keys() {
+        for (name, byte) in [
+            ("ctrl-t", 0x14),
+            ("Ctrl-]", 0x1d),
+            ("^a", 0x01),
+            ("^?", 0x7f),
+        ] {
+            let escape_key = valid_escape_key(name).unwrap();
+            assert_eq!(escape_key.value, byte);
+            assert_eq!(escape_key.name, name);
+        }
+    }
+
+    #[test]
+    fn rejects_other_escape_keys() {
+        for name in ["t", "ctrl-", "ctrl-tt", "alt-t", "^1"] {
+            assert!(valid_escape_key(name).is_err(), "{}", name);
+        }
+    }
+}
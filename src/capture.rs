@@ -0,0 +1,271 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor, queue,
+    style::Print,
+    terminal::{Clear, ClearType},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::cast::stdout;
+use crate::encoding::Encoding;
+use crate::line_ending::LineEnding;
+use crate::rotation::{RotatingFile, Rotation};
+use crate::rx_display::{ControlChars, RxDisplay, RxOutput};
+
+/// Bytes in the same direction that arrive within this window are recorded as one chunk.
+const CHUNK_WINDOW: Duration = Duration::from_millis(10);
+const CHUNK_MAX_LEN: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+/// One line of a capture file, e.g. `{"t":1.204518,"dir":"rx","data":"48690d0a"}`
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    /// Seconds since the capture started
+    t: f64,
+    dir: Direction,
+    /// The bytes, hex encoded
+    data: String,
+}
+
+/// Bytes received or sent in one direction, starting at `time`.
+pub struct PendingChunk {
+    pub time: Instant,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+/// Add bytes to the pending chunk, returning the previous chunk when it is complete.
+pub fn coalesce(
+    pending: &mut Option<PendingChunk>,
+    direction: Direction,
+    bytes: &[u8],
+) -> Option<PendingChunk> {
+    let now = Instant::now();
+    if let Some(chunk) = pending {
+        if chunk.direction == direction
+            && now - chunk.time < CHUNK_WINDOW
+            && chunk.data.len() < CHUNK_MAX_LEN
+        {
+            chunk.data.extend_from_slice(bytes);
+            return None;
+        }
+    }
+    pending.replace(PendingChunk {
+        time: now,
+        direction,
+        data: bytes.to_vec(),
+    })
+}
+
+/// A file that both directions of the session are recorded to.
+pub trait Recorder {
+    fn record(&mut self, direction: Direction, bytes: &[u8]) -> io::Result<()>;
+}
+
+/// A JSON-lines recording of both directions of a session with monotonic timestamps.
+pub struct Capture {
+    writer: RotatingFile,
+    start: Instant,
+    pending: Option<PendingChunk>,
+}
+
+impl Capture {
+    pub fn create(path: &Path, rotation: Rotation) -> io::Result<Capture> {
+        Ok(Capture {
+            writer: RotatingFile::create(path, rotation, &[])?,
+            start: Instant::now(),
+            pending: None,
+        })
+    }
+
+    fn write_chunk(&mut self, pending: PendingChunk) -> io::Result<()> {
+        let chunk = Chunk {
+            t: (pending.time - self.start).as_secs_f64(),
+            dir: pending.direction,
+            data: pending
+                .data
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        };
+        // one write for the line, so that it is not split across files
+        let mut line = serde_json::to_vec(&chunk)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)
+    }
+
+    /// Write out the chunk that is still collecting bytes.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.pending.take() {
+            Some(pending) => self.write_chunk(pending),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Recorder for Capture {
+    fn record(&mut self, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        match coalesce(&mut self.pending, direction, bytes) {
+            Some(complete) => self.write_chunk(complete),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Play the received side of a capture through the normal renderer, `speed` times faster than
+/// it was recorded.
+pub fn replay(
+    path: &Path,
+    speed: f64,
+    encoding: Encoding,
+    rx_eol: LineEnding,
+    control_chars: ControlChars,
+) -> io::Result<()> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut rx_display = RxDisplay::new(false, encoding, rx_eol, control_chars, Vec::new());
+    let mut stdout = stdout();
+    let start = Instant::now();
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |reason: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", line_number + 1, reason),
+            )
+        };
+        let chunk: Chunk = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+        let data = decode_hex(&chunk.data).ok_or_else(|| invalid(String::from("bad hex data")))?;
+
+        let due = match chunk.t.is_finite() && chunk.t >= 0.0 {
+            true => Duration::try_from_secs_f64(chunk.t / speed).ok(),
+            false => None,
+        };
+        let due = due.ok_or_else(|| invalid(String::from("bad time")))?;
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            stdout.flush()?;
+            thread::sleep(wait);
+        }
+
+        if chunk.dir == Direction::Tx {
+            continue;
+        }
+        for byte in data {
+            for output in rx_display.render(byte) {
+                match output {
+                    RxOutput::Print(text) => queue!(stdout, Print(text))?,
+                    RxOutput::Newline => queue!(stdout, Print('\n'))?,
+                    RxOutput::CursorPositionReport => {}
+                    // the replay has the whole screen
+                    RxOutput::ClearScreen => queue!(stdout, Clear(ClearType::All))?,
+                    RxOutput::ClearBelow => queue!(stdout, Clear(ClearType::FromCursorDown))?,
+                    RxOutput::MoveTo(row, col) => queue!(stdout, cursor::MoveTo(col, row))?,
+                    RxOutput::SaveCursor => queue!(stdout, cursor::SavePosition)?,
+                    RxOutput::RestoreCursor => queue!(stdout, cursor::RestorePosition)?,
+                };
+            }
+        }
+    }
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesces_bytes_in_one_direction() {
+        let mut pending = None;
+        assert!(coalesce(&mut pending, Direction::Rx, b"ab").is_none());
+        assert!(coalesce(&mut pending, Direction::Rx, b"c").is_none());
+
+        let complete = coalesce(&mut pending, Direction::Tx, b"x").unwrap();
+        assert_eq!(complete.direction, Direction::Rx);
+        assert_eq!(complete.data, b"abc");
+        let pending = pending.unwrap();
+        assert_eq!(pending.direction, Direction::Tx);
+        assert_eq!(pending.data, b"x");
+    }
+
+    #[test]
+    fn starts_a_chunk_after_the_window() {
+        let mut pending = None;
+        coalesce(&mut pending, Direction::Rx, b"a");
+        thread::sleep(CHUNK_WINDOW * 2);
+        let complete = coalesce(&mut pending, Direction::Rx, b"b").unwrap();
+        assert_eq!(complete.data, b"a");
+        assert_eq!(pending.unwrap().data, b"b");
+    }
+
+    #[test]
+    fn starts_a_chunk_once_full() {
+        let mut pending = None;
+        coalesce(&mut pending, Direction::Rx, &[0; CHUNK_MAX_LEN]);
+        let complete = coalesce(&mut pending, Direction::Rx, b"b").unwrap();
+        assert_eq!(complete.data.len(), CHUNK_MAX_LEN);
+        assert_eq!(pending.unwrap().data, b"b");
+    }
+
+    #[test]
+    fn writes_a_line_per_chunk() {
+        let path = std::env::temp_dir().join(format!("capture-{}.jsonl", std::process::id()));
+        let mut capture = Capture::create(&path, Rotation::default()).unwrap();
+        capture.record(Direction::Rx, b"Hi\r\n").unwrap();
+        capture.record(Direction::Tx, b"x").unwrap();
+        drop(capture);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let chunks: Vec<Chunk> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            (chunks[0].dir, chunks[0].data.as_str()),
+            (Direction::Rx, "48690d0a")
+        );
+        assert_eq!(
+            (chunks[1].dir, chunks[1].data.as_str()),
+            (Direction::Tx, "78")
+        );
+        assert!(chunks[0].t <= chunks[1].t);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(decode_hex("48690d0a"), Some(b"Hi\r\n".to_vec()));
+        assert_eq!(decode_hex(""), Some(Vec::new()));
+        assert_eq!(decode_hex("4"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+}
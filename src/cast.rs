@@ -0,0 +1,107 @@
+//! asciicast v2 recording of everything drawn to the terminal, see
+//! https://docs.asciinema.org/manual/asciicast/v2/
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use serde_json::json;
+
+use crate::rotation::{RotatingFile, Rotation};
+
+static RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+
+struct Recording {
+    writer: RotatingFile,
+    size: (u16, u16),
+    /// The start of the current file, each rotated file is a recording of its own
+    start: Instant,
+    /// Bytes written since the last flush, which become one output event
+    pending: Vec<u8>,
+}
+
+impl Recording {
+    fn write_event(&mut self) -> io::Result<()> {
+        // hold back a multi-byte character that was split across flushes
+        let valid_len = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        if valid_len == 0 {
+            return Ok(());
+        }
+        let data: Vec<u8> = self.pending.drain(..valid_len).collect();
+        if self.writer.is_due() {
+            self.writer.set_header(&header(self.size));
+            self.start = Instant::now();
+        }
+        let event = json!([
+            self.start.elapsed().as_secs_f64(),
+            "o",
+            String::from_utf8_lossy(&data)
+        ]);
+        self.writer.write_all(format!("{}\n", event).as_bytes())
+    }
+}
+
+fn header((width, height): (u16, u16)) -> Vec<u8> {
+    let header = json!({
+        "version": 2,
+        "width": width,
+        "height": height,
+        "timestamp": chrono::offset::Utc::now().timestamp(),
+        "env": {"TERM": std::env::var("TERM").unwrap_or_default()},
+    });
+    format!("{}\n", header).into_bytes()
+}
+
+/// Begin recording terminal output to `path` as a `.cast` file.
+pub fn start(path: &Path, size: (u16, u16), rotation: Rotation) -> io::Result<()> {
+    let writer = RotatingFile::create(path, rotation, &header(size))?;
+    *RECORDING.lock().unwrap() = Some(Recording {
+        writer,
+        size,
+        start: Instant::now(),
+        pending: Vec::new(),
+    });
+    Ok(())
+}
+
+/// Standard output that is also written to the recording, if there is one.
+pub struct RecordedStdout(io::Stdout);
+
+pub fn stdout() -> RecordedStdout {
+    RecordedStdout(io::stdout())
+}
+
+impl Write for RecordedStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.0.write(buf)?;
+        // the lock is only taken while writing the recording, whose own error messages go
+        // to the terminal but not to the recording
+        if let Ok(mut recording) = RECORDING.try_lock() {
+            if let Some(recording) = recording.as_mut() {
+                recording.pending.extend_from_slice(&buf[..written]);
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        let Ok(mut recording) = RECORDING.try_lock() else {
+            return Ok(());
+        };
+        if let Some(active_recording) = recording.as_mut() {
+            if active_recording.write_event().is_err() {
+                // a failed recording must not take the terminal down with it
+                *recording = None;
+            }
+        }
+        Ok(())
+    }
+}
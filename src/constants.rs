@@ -3,13 +3,15 @@ use const_format::formatcp;
 pub const ABOUT: &str = "\
     A cross platform CLI for serial devices.\n\
     \n\
-    Press Ctrl-t to bring up the menu and exit.\
+    Press Ctrl-t, or the key set with --escape-key, to bring up the menu and exit.\
 ";
 
 pub const HELP: &str = "\
     TODO: some more detailed usage information.\
 ";
 
+pub const TIMESTAMP_FORMAT: &str = "%H:%M:%S%.3f";
+
 pub const LONG_VERSION: &str = formatcp!(
     "{}
     build:  {} {} {}
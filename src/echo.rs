@@ -0,0 +1,70 @@
+use crossterm::style::{Color::DarkCyan, Stylize};
+
+use crate::encoding::{Decoder, Encoding};
+use crate::rx_display::{ControlChars, RxOutput};
+
+/// Draws sent data among the received data, in its own color, for devices that do not echo.
+pub struct Echo {
+    pub enabled: bool,
+    after_cr: bool,
+    /// Sent bytes are in the TX encoding
+    decoder: Decoder,
+}
+
+impl Echo {
+    pub fn new(enabled: bool, tx_encoding: Encoding) -> Echo {
+        Echo {
+            enabled,
+            after_cr: false,
+            decoder: Decoder::new(tx_encoding),
+        }
+    }
+
+    /// Output for bytes that were sent, each CR or LF is a newline and a CRLF is just one.
+    pub fn render(&mut self, bytes: &[u8]) -> Vec<RxOutput> {
+        let mut outputs = Vec::new();
+        let mut text = String::new();
+        let decoded: String = bytes
+            .iter()
+            .map(|&byte| self.decoder.decode(byte))
+            .collect();
+        for character in decoded.chars() {
+            let after_cr = std::mem::replace(&mut self.after_cr, character == '\r');
+            match character {
+                '\n' if after_cr => {}
+                '\r' | '\n' => {
+                    push_colored(&mut outputs, &mut text);
+                    outputs.push(RxOutput::Print(String::from("\r")));
+                    outputs.push(RxOutput::Newline);
+                }
+                _ => match ControlChars::Caret.marker(character) {
+                    Some(marker) => text.push_str(&marker),
+                    None => text.push(character),
+                },
+            }
+        }
+        push_colored(&mut outputs, &mut text);
+        outputs
+    }
+}
+
+fn push_colored(outputs: &mut Vec<RxOutput>, text: &mut String) {
+    if !text.is_empty() {
+        let colored = std::mem::take(text).with(DarkCyan).to_string();
+        outputs.push(RxOutput::Print(colored));
+    }
+}
+
+/// Handle the menu command `echo on` or `echo off`, returning a message for the UI.
+pub fn echo_command(echo: &mut Echo, argument: &str) -> String {
+    match argument {
+        "on" => echo.enabled = true,
+        "off" => echo.enabled = false,
+        "" => {}
+        _ => return String::from("Usage: echo on|off"),
+    };
+    match echo.enabled {
+        true => String::from("Local echo on"),
+        false => String::from("Local echo off"),
+    }
+}
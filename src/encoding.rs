@@ -0,0 +1,203 @@
+use std::fmt::{Display, Formatter, Result};
+
+/// Shown in place of bytes that are not valid in the encoding.
+pub const REPLACEMENT: char = '\u{FFFD}';
+
+/// Code page 437 from 0x80, the lower half is ASCII.
+const CP437_UPPER: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Latin1,
+    Cp437,
+    Ascii,
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let name = match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "latin1",
+            Encoding::Cp437 => "cp437",
+            Encoding::Ascii => "ascii",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Encoding {
+    /// The bytes for typed text, with `?` for characters the encoding does not have.
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            _ => text
+                .chars()
+                .map(|character| self.encode_char(character))
+                .collect(),
+        }
+    }
+
+    fn encode_char(self, character: char) -> u8 {
+        let code = u32::from(character);
+        match (self, code) {
+            (_, 0x00..=0x7f) => code as u8,
+            (Encoding::Latin1, 0x80..=0xff) => code as u8,
+            (Encoding::Cp437, _) => match CP437_UPPER.iter().position(|&c| c == character) {
+                Some(index) => 0x80 + index as u8,
+                None => b'?',
+            },
+            _ => b'?',
+        }
+    }
+}
+
+/// Turns received bytes into characters one byte at a time, holding back incomplete UTF-8.
+pub struct Decoder {
+    pub encoding: Encoding,
+    pending: Vec<u8>,
+    /// Length of the UTF-8 sequence that `pending` is the start of
+    expected_len: usize,
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding) -> Decoder {
+        Decoder {
+            encoding,
+            pending: Vec::with_capacity(4),
+            expected_len: 0,
+        }
+    }
+
+    /// The characters completed by this byte, if any.
+    pub fn decode(&mut self, byte: u8) -> String {
+        match self.encoding {
+            Encoding::Utf8 => self.decode_utf8(byte),
+            Encoding::Latin1 => String::from(byte as char),
+            Encoding::Cp437 => match byte {
+                0x00..=0x7f => String::from(byte as char),
+                _ => String::from(CP437_UPPER[usize::from(byte - 0x80)]),
+            },
+            Encoding::Ascii => match byte {
+                0x00..=0x7f => String::from(byte as char),
+                _ => String::from(REPLACEMENT),
+            },
+        }
+    }
+
+    /// Give up on an incomplete character, e.g. when an escape sequence interrupts it.
+    pub fn flush(&mut self) -> Option<char> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.pending.clear();
+        self.expected_len = 0;
+        Some(REPLACEMENT)
+    }
+
+    fn decode_utf8(&mut self, byte: u8) -> String {
+        let mut decoded = String::new();
+        if !self.pending.is_empty() {
+            if byte & 0xc0 == 0x80 {
+                self.pending.push(byte);
+                if self.pending.len() == self.expected_len {
+                    // overlong encodings and surrogates only show up once complete
+                    match std::str::from_utf8(&self.pending) {
+                        Ok(character) => decoded.push_str(character),
+                        Err(_) => decoded.push(REPLACEMENT),
+                    }
+                    self.pending.clear();
+                    self.expected_len = 0;
+                }
+                return decoded;
+            }
+            // the character was cut short, the byte starts something new
+            decoded.extend(self.flush());
+        }
+
+        self.expected_len = match byte {
+            0x00..=0x7f => {
+                decoded.push(byte as char);
+                return decoded;
+            }
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => {
+                decoded.push(REPLACEMENT);
+                return decoded;
+            }
+        };
+        self.pending.push(byte);
+        decoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(encoding: Encoding, bytes: &[u8]) -> String {
+        let mut decoder = Decoder::new(encoding);
+        bytes.iter().map(|&byte| decoder.decode(byte)).collect()
+    }
+
+    #[test]
+    fn utf8_round_trip() {
+        let text = "ok é ✓ 🎉\r\n";
+        assert_eq!(decode(Encoding::Utf8, &Encoding::Utf8.encode(text)), text);
+    }
+
+    #[test]
+    fn single_byte_round_trips() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for encoding in [Encoding::Latin1, Encoding::Cp437] {
+            let text = decode(encoding, &bytes);
+            assert_eq!(text.chars().count(), 256);
+            assert_eq!(encoding.encode(&text), bytes, "{}", encoding);
+        }
+    }
+
+    #[test]
+    fn replaces_characters_the_encoding_lacks() {
+        assert_eq!(Encoding::Ascii.encode("aé"), b"a?");
+        assert_eq!(Encoding::Latin1.encode("a✓"), b"a?");
+        assert_eq!(Encoding::Cp437.encode("½€"), [0xab, b'?']);
+        assert_eq!(
+            decode(Encoding::Ascii, b"a\x80"),
+            format!("a{}", REPLACEMENT)
+        );
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        // cut short by the next character, a lone continuation byte, and an overlong encoding
+        assert_eq!(
+            decode(Encoding::Utf8, b"\xe2\x9ca"),
+            format!("{}a", REPLACEMENT)
+        );
+        assert_eq!(decode(Encoding::Utf8, b"\x80"), String::from(REPLACEMENT));
+        assert_eq!(
+            decode(Encoding::Utf8, b"\xe0\x80\x80"),
+            String::from(REPLACEMENT)
+        );
+    }
+
+    #[test]
+    fn flushes_an_incomplete_character() {
+        let mut decoder = Decoder::new(Encoding::Utf8);
+        assert_eq!(decoder.decode(0xf0), "");
+        assert_eq!(decoder.flush(), Some(REPLACEMENT));
+        assert_eq!(decoder.flush(), None);
+        assert_eq!(decoder.decode(b'a'), "a");
+    }
+}
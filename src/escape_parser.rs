@@ -0,0 +1,363 @@
+//! Splits received bytes into text and whole escape sequences, following the VT500 parser at
+//! https://vt100.net/emu/dec_ansi_parser so that sequences are never cut short or mixed up.
+//!
+//! Sequences are passed to the terminal to interpret, except those that would make it answer
+//! back, since the answer would arrive as keyboard input instead of going to the device, and
+//! those that take over the whole screen, which would wipe the menu, TX pane and status bar:
+//! the scroll region, a full reset and the alternate screen. Cursor position reports, clearing
+//! the screen, moving the cursor to a row and column and saving and restoring the cursor are
+//! returned as their own tokens so that they can be kept to the rows of received data, and so
+//! that our own use of the terminal's saved cursor does not overwrite the device's.
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+const DEL: u8 = 0x7f;
+
+/// Longest sequence that is kept, longer ones (e.g. runaway OSC strings) are dropped.
+const MAX_SEQUENCE_LEN: usize = 4096;
+
+#[derive(Debug, PartialEq)]
+pub enum Token {
+    /// A printable or control byte outside of any sequence
+    Byte(u8),
+    /// A complete escape sequence, including the ESC
+    Sequence(String),
+    /// `CSI 6n`, the device asking where its cursor is
+    CursorPositionRequest,
+    /// `CSI 2J` or `CSI 3J`
+    ClearScreen,
+    /// `CSI J` or `CSI 0J`, from the cursor to the end of the screen
+    ClearBelow,
+    /// `CSI row;col H` or `f`, counted from 1
+    CursorPosition(u16, u16),
+    /// `ESC 7`, `CSI s` or `CSI ?1048h`
+    SaveCursor,
+    /// `ESC 8`, `CSI u` or `CSI ?1048l`
+    RestoreCursor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    /// Control Sequence Introducer, `ESC [`
+    Csi,
+    /// Operating System Command, `ESC ]`, ended by BEL or ST
+    Osc,
+    /// DCS, SOS, PM or APC string, ended by ST
+    String,
+    /// ESC received within a string, which is the String Terminator if followed by `\`
+    StringEscape,
+}
+
+pub struct EscapeParser {
+    state: State,
+    sequence: Vec<u8>,
+}
+
+impl EscapeParser {
+    pub fn new() -> EscapeParser {
+        EscapeParser {
+            state: State::Ground,
+            sequence: Vec::with_capacity(16),
+        }
+    }
+
+    /// Abandon any partial sequence.
+    pub fn reset(&mut self) {
+        self.state = State::Ground;
+        self.sequence.clear();
+    }
+
+    /// Returns a token once the byte completes one, C0 controls within a sequence are
+    /// returned immediately as a terminal would execute them.
+    pub fn parse(&mut self, byte: u8) -> Option<Token> {
+        match (self.state, byte) {
+            (State::Ground, ESC) => self.begin(State::Escape, byte),
+            (State::Ground, _) => Some(Token::Byte(byte)),
+
+            // strings end with BEL (OSC only) or ST, anything else is part of the string
+            (State::Osc, BEL) => self.finish(byte),
+            (State::Osc, ESC) | (State::String, ESC) => self.extend(State::StringEscape, byte),
+            (State::Osc, CAN | SUB) | (State::String, CAN | SUB) => self.abort(),
+            (State::Osc, _) | (State::String, _) => self.extend(self.state, byte),
+            (State::StringEscape, b'\\') => self.finish(byte),
+            (State::StringEscape, _) => {
+                // not a terminator, the string is abandoned and a new sequence begins
+                self.reset();
+                self.parse(ESC);
+                self.parse(byte)
+            }
+
+            // a new ESC or CAN/SUB cancels the sequence in progress
+            (_, ESC) => self.begin(State::Escape, byte),
+            (_, CAN | SUB) => self.abort(),
+            (_, DEL) => None,
+            (_, 0x00..=0x1f) => Some(Token::Byte(byte)),
+
+            (State::Escape, b'[') => self.extend(State::Csi, byte),
+            (State::Escape, b']') => self.extend(State::Osc, byte),
+            (State::Escape, b'P' | b'X' | b'^' | b'_') => self.extend(State::String, byte),
+            (State::Escape | State::EscapeIntermediate, 0x20..=0x2f) => {
+                self.extend(State::EscapeIntermediate, byte)
+            }
+            (State::Escape | State::EscapeIntermediate, 0x30..=0x7e) => self.finish(byte),
+
+            // parameters and intermediates, then the final byte
+            (State::Csi, 0x20..=0x3f) => self.extend(State::Csi, byte),
+            (State::Csi, 0x40..=0x7e) => self.finish(byte),
+
+            // bytes outside of 7 bit ASCII are not part of any sequence
+            (_, _) => {
+                self.reset();
+                Some(Token::Byte(byte))
+            }
+        }
+    }
+
+    fn begin(&mut self, state: State, byte: u8) -> Option<Token> {
+        self.sequence.clear();
+        self.extend(state, byte)
+    }
+
+    fn extend(&mut self, state: State, byte: u8) -> Option<Token> {
+        self.state = state;
+        // keep one byte past the limit so that finish knows to drop it
+        if self.sequence.len() <= MAX_SEQUENCE_LEN {
+            self.sequence.push(byte);
+        }
+        None
+    }
+
+    fn abort(&mut self) -> Option<Token> {
+        self.reset();
+        None
+    }
+
+    fn finish(&mut self, byte: u8) -> Option<Token> {
+        self.sequence.push(byte);
+        let sequence = String::from_utf8_lossy(&self.sequence).to_string();
+        let too_long = self.sequence.len() > MAX_SEQUENCE_LEN;
+        self.reset();
+        match too_long {
+            true => None,
+            false => token(sequence),
+        }
+    }
+}
+
+/// The token for a complete sequence, if it is to be kept.
+fn token(sequence: String) -> Option<Token> {
+    if let Some(csi) = sequence.strip_prefix("\x1b[") {
+        let (parameters, final_byte) = csi.split_at(csi.len() - 1);
+        match (parameters, final_byte) {
+            ("6", "n") => return Some(Token::CursorPositionRequest),
+            ("2" | "3", "J") => return Some(Token::ClearScreen),
+            ("" | "0", "J") => return Some(Token::ClearBelow),
+            (_, "H" | "f") if !parameters.starts_with(['<', '=', '>', '?']) => {
+                // missing and zero parameters both mean the first row or column
+                let mut numbers = parameters
+                    .split(';')
+                    .map(|number| number.parse::<u16>().unwrap_or(1).max(1));
+                let row = numbers.next().unwrap_or(1);
+                let col = numbers.next().unwrap_or(1);
+                return Some(Token::CursorPosition(row, col));
+            }
+            ("", "s") | ("?1048", "h") => return Some(Token::SaveCursor),
+            ("", "u") | ("?1048", "l") => return Some(Token::RestoreCursor),
+            // the scroll region is set by us, to keep the rows below the received data in place
+            (_, "r") => return None,
+            // the alternate screen would hide the menu, panes and status bar
+            (_, "h" | "l") if is_alternate_screen(parameters) => return None,
+            _ => {}
+        }
+    }
+    match sequence.as_str() {
+        "\x1b7" => Some(Token::SaveCursor),
+        "\x1b8" => Some(Token::RestoreCursor),
+        // a full reset would clear the screen and the scroll region
+        "\x1bc" => None,
+        _ if answers_back(&sequence) => None,
+        _ => Some(Token::Sequence(sequence)),
+    }
+}
+
+/// True for private modes that switch to or from the alternate screen.
+fn is_alternate_screen(parameters: &str) -> bool {
+    parameters.strip_prefix('?').is_some_and(|modes| {
+        modes
+            .split(';')
+            .any(|mode| matches!(mode, "47" | "1047" | "1049"))
+    })
+}
+
+/// The text without its escape sequences and control characters, as it reads on screen, e.g.
+/// for a line that was printed highlighted.
+pub fn plain_text(text: &str) -> String {
+    if !text.contains('\x1b') {
+        return text.chars().filter(|c| !c.is_control()).collect();
+    }
+    let mut parser = EscapeParser::new();
+    let bytes: Vec<u8> = text
+        .bytes()
+        .filter_map(|byte| match parser.parse(byte) {
+            Some(Token::Byte(byte)) => Some(byte),
+            _ => None,
+        })
+        .collect();
+    String::from_utf8_lossy(&bytes)
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect()
+}
+
+/// True for queries and modes that make the terminal write to its input: device status and
+/// attribute reports, window reports, color and capability queries, mouse and focus reporting.
+fn answers_back(sequence: &str) -> bool {
+    if let Some(csi) = sequence.strip_prefix("\x1b[") {
+        let final_byte = csi.chars().last().unwrap_or_default();
+        let parameters = &csi[..csi.len() - 1];
+        return match final_byte {
+            'n' | 'c' | 'x' | 't' => true,
+            'p' => parameters.ends_with('$'),
+            'h' | 'l' => parameters.strip_prefix('?').is_some_and(|modes| {
+                modes.split(';').any(|mode| {
+                    matches!(
+                        mode,
+                        "9" | "1000" | "1001" | "1002" | "1003" | "1004" | "1005" | "1006" | "1015"
+                    )
+                })
+            }),
+            _ => false,
+        };
+    }
+    if let Some(osc) = sequence.strip_prefix("\x1b]") {
+        return osc.contains(";?");
+    }
+    sequence.starts_with("\x1bP$q") || sequence.starts_with("\x1bP+q") || sequence == "\x1bZ"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(bytes: &[u8]) -> Vec<Token> {
+        let mut parser = EscapeParser::new();
+        bytes
+            .iter()
+            .filter_map(|&byte| parser.parse(byte))
+            .collect()
+    }
+
+    fn sequence(text: &str) -> Token {
+        Token::Sequence(String::from(text))
+    }
+
+    #[test]
+    fn splits_text_and_sequences() {
+        assert_eq!(
+            tokens(b"a\x1b[1;31mb\x1b(B"),
+            vec![
+                Token::Byte(b'a'),
+                sequence("\x1b[1;31m"),
+                Token::Byte(b'b'),
+                sequence("\x1b(B"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ends_strings_with_bel_or_st() {
+        assert_eq!(
+            tokens(b"\x1b]0;title\x07\x1bP1$r\x1b\\"),
+            vec![sequence("\x1b]0;title\x07"), sequence("\x1bP1$r\x1b\\")]
+        );
+    }
+
+    #[test]
+    fn executes_controls_within_a_sequence() {
+        assert_eq!(
+            tokens(b"\x1b[1\r2m"),
+            vec![Token::Byte(b'\r'), sequence("\x1b[12m")]
+        );
+    }
+
+    #[test]
+    fn cancels_sequences() {
+        assert_eq!(tokens(b"\x1b[12\x18x"), vec![Token::Byte(b'x')]);
+        assert_eq!(tokens(b"\x1b[12\x1b[m"), vec![sequence("\x1b[m")]);
+    }
+
+    #[test]
+    fn drops_queries_that_answer_back() {
+        for query in [
+            "\x1b[c",
+            "\x1b[>c",
+            "\x1b[5n",
+            "\x1b[?1000h",
+            "\x1b[?2$p",
+            "\x1b]10;?\x07",
+            "\x1bP$qm\x1b\\",
+            "\x1bZ",
+        ] {
+            assert_eq!(tokens(query.as_bytes()), vec![], "{:?}", query);
+        }
+        assert_eq!(tokens(b"\x1b[?25h"), vec![sequence("\x1b[?25h")]);
+    }
+
+    #[test]
+    fn drops_sequences_that_take_over_the_screen() {
+        assert_eq!(tokens(b"\x1b[1;20r\x1b[r\x1bc"), vec![]);
+        assert_eq!(
+            tokens(b"\x1b[?1049h\x1b[?1049l\x1b[?47h\x1b[?1047l\x1b[?25;1049h"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn keeps_the_saved_cursor_apart() {
+        assert_eq!(
+            tokens(b"\x1b7\x1b8\x1b[s\x1b[u\x1b[?1048h\x1b[?1048l"),
+            vec![
+                Token::SaveCursor,
+                Token::RestoreCursor,
+                Token::SaveCursor,
+                Token::RestoreCursor,
+                Token::SaveCursor,
+                Token::RestoreCursor,
+            ]
+        );
+        assert_eq!(tokens(b"\x1b[1s"), vec![sequence("\x1b[1s")]);
+    }
+
+    #[test]
+    fn keeps_screen_control_for_the_rx_rows() {
+        assert_eq!(
+            tokens(b"\x1b[6n\x1b[2J\x1b[3J\x1b[H\x1b[5;10H\x1b[0;7f\x1b[;3H"),
+            vec![
+                Token::CursorPositionRequest,
+                Token::ClearScreen,
+                Token::ClearScreen,
+                Token::CursorPosition(1, 1),
+                Token::CursorPosition(5, 10),
+                Token::CursorPosition(1, 7),
+                Token::CursorPosition(1, 3),
+            ]
+        );
+        assert_eq!(
+            tokens(b"\x1b[J\x1b[0J\x1b[1J"),
+            vec![Token::ClearBelow, Token::ClearBelow, sequence("\x1b[1J")]
+        );
+    }
+
+    #[test]
+    fn drops_sequences_that_are_too_long() {
+        let mut runaway = b"\x1b]0;".to_vec();
+        runaway.extend(vec![b'x'; MAX_SEQUENCE_LEN]);
+        runaway.extend(b"\x07a");
+        assert_eq!(tokens(&runaway), vec![Token::Byte(b'a')]);
+    }
+}
@@ -0,0 +1,148 @@
+//! A JSON-lines log of connection lifecycle events, e.g.
+//! `{"time":"2023-01-14T09:30:00.123-08:00","event":"disconnected","port":"COM3",...,"error_kind":"permission denied","connected_secs":3600.5}`
+
+use std::{
+    io::{self, ErrorKind, Write},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use chrono::SecondsFormat;
+use serde_json::{json, Map, Value};
+use serialport::{available_ports, SerialPortType};
+
+use crate::log_to_ui::{log_to_ui, print_log_to_stdout};
+use crate::rotation::{RotatingFile, Rotation};
+use crate::Args;
+
+static EVENT_LOG: Mutex<Option<EventLog>> = Mutex::new(None);
+
+pub enum Event<'a> {
+    Waiting(Option<ErrorKind>),
+    Connected,
+    Disconnected(ErrorKind),
+    RxError(&'a io::Error),
+    TxError(&'a io::Error),
+    KeypressError,
+}
+
+struct EventLog {
+    file: RotatingFile,
+    /// Port name and settings, included in every event
+    port: Map<String, Value>,
+    connected_since: Option<Instant>,
+    disconnected_since: Option<Instant>,
+}
+
+pub fn start(path: &Path, args: &Args, rotation: Rotation) -> io::Result<()> {
+    let port = json!({
+        "port": args.port(),
+        "baud": args.baud,
+        "data_bits": args.data_bits.name,
+        "parity": args.parity.name,
+        "stop_bits": args.stop_bits.name,
+        "flow_control": args.flow_control.name,
+    });
+    *EVENT_LOG.lock().unwrap() = Some(EventLog {
+        file: RotatingFile::append(path, rotation)?,
+        port: port.as_object().unwrap().clone(),
+        connected_since: None,
+        disconnected_since: Some(Instant::now()),
+    });
+    Ok(())
+}
+
+/// USB details of the port, to tell adapters apart.
+fn usb_info(port_name: &str) -> Option<Value> {
+    let port = available_ports()
+        .ok()?
+        .into_iter()
+        .find(|port| port.port_name == port_name)?;
+    match port.port_type {
+        SerialPortType::UsbPort(info) => Some(json!({
+            "vid": format!("0x{:04x}", info.vid),
+            "pid": format!("0x{:04x}", info.pid),
+            "serial_number": info.serial_number,
+            "manufacturer": info.manufacturer,
+            "product": info.product,
+        })),
+        _ => None,
+    }
+}
+
+fn error_kind(kind: ErrorKind) -> Value {
+    Value::String(kind.to_string())
+}
+
+fn insert_error(details: &mut Map<String, Value>, error: &io::Error) {
+    details.insert(String::from("error_kind"), error_kind(error.kind()));
+    details.insert(String::from("error"), Value::String(error.to_string()));
+}
+
+pub fn log_event(event: Event) {
+    let mut event_log = EVENT_LOG.lock().unwrap();
+    let active_log = match event_log.as_mut() {
+        Some(active_log) => active_log,
+        None => return,
+    };
+
+    let mut record = Map::new();
+    record.insert(
+        String::from("time"),
+        Value::String(chrono::offset::Local::now().to_rfc3339_opts(SecondsFormat::Millis, false)),
+    );
+    let mut details = Map::new();
+    let name = match event {
+        Event::Waiting(kind) => {
+            if let Some(kind) = kind {
+                details.insert(String::from("error_kind"), error_kind(kind));
+            }
+            "waiting"
+        }
+        Event::Connected => {
+            if let Some(since) = active_log.disconnected_since.take() {
+                details.insert(
+                    String::from("waited_secs"),
+                    json!(since.elapsed().as_secs_f64()),
+                );
+            }
+            if let Some(usb) = usb_info(active_log.port["port"].as_str().unwrap_or_default()) {
+                details.insert(String::from("usb"), usb);
+            }
+            active_log.connected_since = Some(Instant::now());
+            "connected"
+        }
+        Event::Disconnected(kind) => {
+            details.insert(String::from("error_kind"), error_kind(kind));
+            if let Some(since) = active_log.connected_since.take() {
+                details.insert(
+                    String::from("connected_secs"),
+                    json!(since.elapsed().as_secs_f64()),
+                );
+            }
+            active_log.disconnected_since = Some(Instant::now());
+            "disconnected"
+        }
+        Event::RxError(error) => {
+            insert_error(&mut details, error);
+            "rx_error"
+        }
+        Event::TxError(error) => {
+            insert_error(&mut details, error);
+            "tx_error"
+        }
+        Event::KeypressError => "keypress_error",
+    };
+    record.insert(String::from("event"), Value::String(String::from(name)));
+    record.extend(active_log.port.clone());
+    record.extend(details);
+
+    // one write per line so that rotation never splits an event
+    let line = format!("{}\n", Value::Object(record));
+    if let Err(error) = active_log.file.write_all(line.as_bytes()) {
+        // the UI carries on without the event log
+        log_to_ui!("Event log error '{}', event logging stopped", error);
+        *event_log = None;
+    }
+}
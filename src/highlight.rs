@@ -0,0 +1,152 @@
+//! Colors the parts of received lines that match user rules, e.g. `--highlight 'ERR|FAIL=red'`
+//! or `--highlight 'WARN=black:yellow'` for a foreground and background color.
+
+use std::{fs, path::Path};
+
+use crossterm::style::{style, Color, Stylize};
+use regex::Regex;
+
+#[derive(Clone, Debug)]
+pub struct HighlightRule {
+    pattern: Regex,
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+fn parse_color(name: &str) -> Result<Option<Color>, String> {
+    match name.trim() {
+        "" => Ok(None),
+        name => match Color::try_from(name) {
+            Ok(color) => Ok(Some(color)),
+            Err(()) => Err(format!("unknown color '{}'", name)),
+        },
+    }
+}
+
+impl HighlightRule {
+    /// Parse `PATTERN=FOREGROUND[:BACKGROUND]`, split at the last `=` so that patterns may contain one.
+    pub fn parse(rule: &str) -> Result<HighlightRule, String> {
+        let (pattern, colors) = match rule.rsplit_once('=') {
+            Some(split) => split,
+            None => return Err(String::from("expected PATTERN=COLOR")),
+        };
+        let (foreground, background) = colors.split_once(':').unwrap_or((colors, ""));
+        let highlight_rule = HighlightRule {
+            pattern: Regex::new(pattern).map_err(|error| error.to_string())?,
+            foreground: parse_color(foreground)?,
+            background: parse_color(background)?,
+        };
+        if highlight_rule.foreground.is_none() && highlight_rule.background.is_none() {
+            return Err(String::from("expected a foreground or background color"));
+        }
+        Ok(highlight_rule)
+    }
+}
+
+/// Read one rule per line, skipping blank lines and `#` comments.
+pub fn load_rules(path: &Path) -> Result<Vec<HighlightRule>, String> {
+    let contents = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut rules = Vec::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let rule = HighlightRule::parse(line)
+            .map_err(|error| format!("line {}: {}", line_number + 1, error))?;
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+/// The line from byte `from` on with every match styled, matching against the whole line so
+/// that a match begun in the part already printed carries on. Where matches overlap the
+/// earlier rule wins.
+pub fn highlight(rules: &[HighlightRule], line: &str, from: usize) -> String {
+    // the rule that styles each byte of the line
+    let mut styled_by: Vec<Option<usize>> = vec![None; line.len()];
+    for (index, rule) in rules.iter().enumerate() {
+        for found in rule.pattern.find_iter(line) {
+            for styled in &mut styled_by[found.range()] {
+                styled.get_or_insert(index);
+            }
+        }
+    }
+    if styled_by[from..].iter().all(Option::is_none) {
+        return line[from..].to_string();
+    }
+
+    let mut highlighted = String::with_capacity((line.len() - from) * 2);
+    let mut run_start = from;
+    for end in line[from..]
+        .char_indices()
+        .map(|(i, _)| from + i)
+        .skip(1)
+        .chain([line.len()])
+    {
+        if end < line.len() && styled_by[end] == styled_by[run_start] {
+            continue;
+        }
+        let run = &line[run_start..end];
+        match styled_by[run_start] {
+            Some(index) => {
+                let mut styled = style(run);
+                if let Some(foreground) = rules[index].foreground {
+                    styled = styled.with(foreground);
+                }
+                if let Some(background) = rules[index].background {
+                    styled = styled.on(background);
+                }
+                highlighted.push_str(&styled.to_string());
+            }
+            None => highlighted.push_str(run),
+        }
+        run_start = end;
+    }
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styles_matches_from_an_offset() {
+        let rules = vec![HighlightRule::parse("ERR\\w*=red").unwrap()];
+        let red = |text: &str| style(text).with(Color::Red).to_string();
+
+        assert_eq!(highlight(&rules, "ok", 0), "ok");
+        assert_eq!(
+            highlight(&rules, "an ERROR!", 0),
+            format!("an {}!", red("ERROR"))
+        );
+        // a match begun before the offset carries on after it
+        assert_eq!(
+            highlight(&rules, "an ERROR!", 5),
+            format!("{}!", red("ROR"))
+        );
+        assert_eq!(highlight(&rules, "an ERROR!", 9), "");
+    }
+
+    #[test]
+    fn the_earlier_rule_wins() {
+        let rules = vec![
+            HighlightRule::parse("AB=red").unwrap(),
+            HighlightRule::parse("BC=:blue").unwrap(),
+        ];
+        let expected = format!(
+            "{}{}",
+            style("AB").with(Color::Red),
+            style("C").on(Color::Blue)
+        );
+        assert_eq!(highlight(&rules, "ABC", 0), expected);
+    }
+
+    #[test]
+    fn rejects_rules_without_colors() {
+        assert!(HighlightRule::parse("ERR").is_err());
+        assert!(HighlightRule::parse("ERR=").is_err());
+        assert!(HighlightRule::parse("ERR=nocolor").is_err());
+        assert!(HighlightRule::parse("a=b=red").is_ok());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+use crate::escape_parser::plain_text;
+use crate::rx_display::RxOutput;
+
+/// Received output held back while the pager or a popup covers the screen, at most as many lines
+/// of it as the history keeps.
+pub struct DeferredOutput {
+    outputs: VecDeque<RxOutput>,
+    lines: usize,
+    max_lines: usize,
+}
+
+impl DeferredOutput {
+    pub fn new(max_lines: usize) -> DeferredOutput {
+        DeferredOutput {
+            outputs: VecDeque::new(),
+            lines: 0,
+            max_lines: max_lines.max(1),
+        }
+    }
+
+    /// Add output, dropping the oldest lines beyond `max_lines`.
+    pub fn push(&mut self, outputs: Vec<RxOutput>) {
+        for output in outputs {
+            if let RxOutput::Newline = output {
+                self.lines += 1;
+            }
+            self.outputs.push_back(output);
+        }
+        while self.lines > self.max_lines {
+            if let Some(RxOutput::Newline) = self.outputs.pop_front() {
+                self.lines -= 1;
+            }
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<RxOutput> {
+        self.lines = 0;
+        self.outputs.drain(..).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines = 0;
+        self.outputs.clear();
+    }
+}
+
+/// The most recent lines of received data as they were displayed, without escape sequences.
+///
+/// Lines are numbered from the first line received, so a line keeps its index as older lines
+/// are dropped.
+pub struct History {
+    lines: VecDeque<String>,
+    current: String,
+    max_lines: usize,
+    /// Lines dropped from the front, the index of the oldest line kept
+    dropped: usize,
+}
+
+impl History {
+    pub fn new(max_lines: usize) -> History {
+        History {
+            lines: VecDeque::with_capacity(max_lines.min(1024)),
+            current: String::new(),
+            max_lines: max_lines.max(1),
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, output: &RxOutput) {
+        match output {
+            RxOutput::Newline => {
+                if self.lines.len() == self.max_lines {
+                    self.lines.pop_front();
+                    self.dropped += 1;
+                }
+                self.lines.push_back(std::mem::take(&mut self.current));
+            }
+            RxOutput::ClearScreen
+            | RxOutput::ClearBelow
+            | RxOutput::MoveTo(..)
+            | RxOutput::SaveCursor
+            | RxOutput::RestoreCursor
+            | RxOutput::CursorPositionReport => {}
+            // escape sequences only mean something to the terminal, e.g. highlighting
+            RxOutput::Print(text) => self.current.push_str(&plain_text(text)),
+        }
+    }
+
+    /// The number of lines, counting the line that is still being received.
+    pub fn line_count(&self) -> usize {
+        self.lines.len() + 1
+    }
+
+    /// The index of the oldest line kept.
+    pub fn first(&self) -> usize {
+        self.dropped
+    }
+
+    /// The index of the line that is still being received.
+    pub fn last(&self) -> usize {
+        self.dropped + self.lines.len()
+    }
+
+    /// A line by index, empty if it has been dropped.
+    pub fn line(&self, index: usize) -> &str {
+        match index.checked_sub(self.dropped) {
+            Some(offset) if offset < self.lines.len() => &self.lines[offset],
+            Some(_) => &self.current,
+            None => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outputs(text: &str) -> Vec<RxOutput> {
+        text.split_inclusive('\n')
+            .flat_map(|line| match line.strip_suffix('\n') {
+                Some(line) => vec![RxOutput::Print(String::from(line)), RxOutput::Newline],
+                None => vec![RxOutput::Print(String::from(line))],
+            })
+            .collect()
+    }
+
+    fn printed(outputs: Vec<RxOutput>) -> String {
+        outputs
+            .into_iter()
+            .map(|output| match output {
+                RxOutput::Print(text) => text,
+                RxOutput::Newline => String::from("\n"),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn indexes_lines_from_the_first_received() {
+        let mut history = History::new(2);
+        for output in outputs("a\nb\nc\nd") {
+            history.push(&output);
+        }
+        assert_eq!(history.line_count(), 3);
+        assert_eq!((history.first(), history.last()), (1, 3));
+        // the oldest line was dropped to keep two complete lines
+        assert_eq!(history.line(0), "");
+        assert_eq!(history.line(1), "b");
+        assert_eq!(history.line(2), "c");
+        assert_eq!(history.line(3), "d");
+    }
+
+    #[test]
+    fn keeps_text_without_screen_control() {
+        let mut history = History::new(10);
+        history.push(&RxOutput::Print(String::from("\x1b[31mred\x1b[m")));
+        history.push(&RxOutput::MoveTo(0, 0));
+        history.push(&RxOutput::Newline);
+        assert_eq!(history.line(0), "red");
+    }
+
+    #[test]
+    fn caps_deferred_output_to_the_newest_lines() {
+        let mut deferred = DeferredOutput::new(2);
+        deferred.push(outputs("a\nb\n"));
+        deferred.push(outputs("c\nd"));
+        assert_eq!(printed(deferred.take()), "b\nc\nd");
+        assert_eq!(printed(deferred.take()), "");
+
+        deferred.push(outputs("e\n"));
+        deferred.clear();
+        deferred.push(outputs("f\ng\n"));
+        assert_eq!(printed(deferred.take()), "f\ng\n");
+    }
+}
@@ -1,41 +1,93 @@
-use std::io;
+use std::{
+    io,
+    sync::atomic::{AtomicU8, Ordering::Relaxed},
+};
 
 use crossterm::event::{Event as CrosstermEvent, KeyCode, KeyModifiers};
 
+use crate::encoding::Encoding;
+use crate::line_ending::LineEnding;
+use crate::paste;
+
+/// The control byte of the key that opens the menu, Ctrl-t unless set with --escape-key
+static ESCAPE_KEY: AtomicU8 = AtomicU8::new(0x14);
+
+pub fn set_escape_key(byte: u8) {
+    ESCAPE_KEY.store(byte, Relaxed);
+}
+
+pub fn escape_key() -> u8 {
+    ESCAPE_KEY.load(Relaxed)
+}
+
+/// How the menu key is shown, e.g. `Ctrl-t` or `Ctrl-]`.
+pub fn escape_key_name() -> String {
+    match escape_key() {
+        byte @ 0x01..=0x1a => format!("Ctrl-{}", (byte - 1 + b'a') as char),
+        0x7f => String::from("Ctrl-?"),
+        byte => format!("Ctrl-{}", (byte + 0x40) as char),
+    }
+}
+
+/// The byte a terminal sends for Ctrl and a key: a letter, `@ [ \ ] ^ _ ?`, or the digits and
+/// space that crossterm reports for some of them, e.g. Ctrl-] arrives as Ctrl-5.
+pub fn control_byte(key: char) -> Option<u8> {
+    match key.to_ascii_lowercase() {
+        key @ 'a'..='z' => Some(key as u8 - b'a' + 1),
+        '@' | ' ' | '2' => Some(0x00),
+        '[' | '3' => Some(0x1b),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '7' => Some(0x1f),
+        '?' | '8' => Some(0x7f),
+        _ => None,
+    }
+}
+
 pub enum KeyboardInputAction {
     Chars(Vec<u8>),
     KeypressError,
     Menu,
+    /// Pasted text, to be sent a chunk at a time
+    Paste(Vec<u8>),
     NoAction,
 }
 
 pub fn handle_keypress_event(
     event: &Option<Result<CrosstermEvent, io::Error>>,
+    tx_eol: LineEnding,
+    tx_encoding: Encoding,
 ) -> KeyboardInputAction {
     match event {
-        Some(Ok(event)) => handle_event(&event),
+        Some(Ok(event)) => handle_event(&event, tx_eol, tx_encoding),
         Some(Err(_)) => KeyboardInputAction::KeypressError,
         None => KeyboardInputAction::KeypressError,
     }
 }
 
-fn handle_event(event: &CrosstermEvent) -> KeyboardInputAction {
-    match event {
+fn handle_event(
+    event: &CrosstermEvent,
+    tx_eol: LineEnding,
+    tx_encoding: Encoding,
+) -> KeyboardInputAction {
+    let action = match event {
         CrosstermEvent::Key(key) => {
             if key.modifiers.contains(KeyModifiers::CONTROL) {
-                if let KeyCode::Char(code) = key.code {
-                    if code == 'c' {
-                        return KeyboardInputAction::Chars(vec![0x03]);
-                    }
-                    if code == 't' {
-                        return KeyboardInputAction::Menu;
-                    }
-                }
-                return KeyboardInputAction::NoAction; // don't handle other Ctrl-* for now
+                return match key.code {
+                    KeyCode::Char(code) => match control_byte(code) {
+                        Some(byte) if byte == escape_key() => KeyboardInputAction::Menu,
+                        Some(byte) => KeyboardInputAction::Chars(vec![byte]),
+                        None => KeyboardInputAction::NoAction,
+                    },
+                    _ => KeyboardInputAction::NoAction,
+                };
             }
             match key.code {
-                KeyCode::Char(code) => KeyboardInputAction::Chars(vec![code as u8]),
-                KeyCode::Enter => KeyboardInputAction::Chars(vec![b'\r']),
+                KeyCode::Char(code) => {
+                    KeyboardInputAction::Chars(tx_encoding.encode(code.encode_utf8(&mut [0; 4])))
+                }
+                KeyCode::Enter => KeyboardInputAction::Chars(tx_eol.bytes().to_vec()),
                 KeyCode::Esc => KeyboardInputAction::Chars(vec![0x1B]),
                 KeyCode::Up => KeyboardInputAction::Chars(vec![0x1B, b'[', b'A']),
                 KeyCode::Down => KeyboardInputAction::Chars(vec![0x1B, b'[', b'B']),
@@ -48,10 +100,53 @@ fn handle_event(event: &CrosstermEvent) -> KeyboardInputAction {
                 _ => KeyboardInputAction::NoAction,
             }
         }
+        CrosstermEvent::Paste(text) => {
+            return KeyboardInputAction::Paste(paste::encode(text, tx_eol, tx_encoding))
+        }
         CrosstermEvent::FocusGained
         | CrosstermEvent::FocusLost
         | CrosstermEvent::Mouse(_)
-        | CrosstermEvent::Resize(_, _)
-        | CrosstermEvent::Paste(_) => KeyboardInputAction::NoAction,
+        | CrosstermEvent::Resize(_, _) => KeyboardInputAction::NoAction,
+    };
+    // the menu key can also be one with a key of its own, e.g. Esc for Ctrl-[
+    match action {
+        KeyboardInputAction::Chars(bytes) if bytes == [escape_key()] => KeyboardInputAction::Menu,
+        action => action,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_bytes_for_letters() {
+        assert_eq!(control_byte('a'), Some(0x01));
+        assert_eq!(control_byte('T'), Some(0x14));
+        assert_eq!(control_byte('z'), Some(0x1a));
+    }
+
+    #[test]
+    fn control_bytes_for_symbols_and_their_digits() {
+        for (symbol, digit, byte) in [
+            ('@', '2', 0x00),
+            ('[', '3', 0x1b),
+            ('\\', '4', 0x1c),
+            (']', '5', 0x1d),
+            ('^', '6', 0x1e),
+            ('_', '7', 0x1f),
+            ('?', '8', 0x7f),
+        ] {
+            assert_eq!(control_byte(symbol), Some(byte));
+            assert_eq!(control_byte(digit), Some(byte));
+        }
+        assert_eq!(control_byte(' '), Some(0x00));
+    }
+
+    #[test]
+    fn no_control_byte_for_other_keys() {
+        for key in ['1', '9', '!', '-', 'é'] {
+            assert_eq!(control_byte(key), None);
+        }
     }
 }
@@ -0,0 +1,262 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind::NotFound, Write},
+    path::Path,
+};
+
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    style::{
+        Color::{DarkGrey, Reset},
+        Print, SetForegroundColor,
+    },
+    terminal::{size, Clear, ClearType::CurrentLine},
+};
+
+use crate::cast::stdout;
+use crate::encoding::Encoding;
+use crate::line_ending::LineEnding;
+use crate::log_to_ui::{log_to_ui, print_log_to_stdout};
+use crate::paste;
+
+/// Lines kept for Up and Down, the history file keeps them all.
+const MAX_HISTORY: usize = 1000;
+
+const PROMPT: &str = "> ";
+
+pub enum LineEditorAction {
+    Edited,
+    /// The line and the TX line ending, for Enter
+    Send(Vec<u8>),
+    /// Keys the editor leaves alone, e.g. the menu key, sent or handled as usual
+    Unhandled,
+}
+
+/// Collects typed characters into a line that is only sent on Enter, for devices with small
+/// buffers or no line editing of their own.
+pub struct LineEditor {
+    pub enabled: bool,
+    line: Vec<char>,
+    /// Index in `line` of the character the cursor is on
+    cursor: usize,
+    history: Vec<String>,
+    /// The history line being shown by Up and Down, and the line that was being typed
+    history_index: Option<usize>,
+    draft: Vec<char>,
+    file: Option<File>,
+}
+
+impl LineEditor {
+    pub fn new(enabled: bool) -> LineEditor {
+        LineEditor {
+            enabled,
+            line: Vec::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: None,
+            draft: Vec::new(),
+            file: None,
+        }
+    }
+
+    /// Load the history from a file, which need not exist yet, and append sent lines to it.
+    pub fn load_history(&mut self, path: &Path) -> io::Result<()> {
+        match fs::read_to_string(path) {
+            Ok(contents) => self.history.extend(contents.lines().map(String::from)),
+            Err(error) if error.kind() == NotFound => {}
+            Err(error) => return Err(error),
+        }
+        let keep_from = self.history.len().saturating_sub(MAX_HISTORY);
+        self.history.drain(..keep_from);
+        self.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        Ok(())
+    }
+
+    pub fn handle_key(
+        &mut self,
+        key: &KeyEvent,
+        tx_eol: LineEnding,
+        tx_encoding: Encoding,
+    ) -> LineEditorAction {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match (key.code, control) {
+            (KeyCode::Char('a'), true) | (KeyCode::Home, _) => self.cursor = 0,
+            (KeyCode::Char('e'), true) | (KeyCode::End, _) => self.cursor = self.line.len(),
+            (KeyCode::Char('u'), true) => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            (KeyCode::Char('w'), true) => {
+                let mut start = self.cursor;
+                while start > 0 && self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            (_, true) => return LineEditorAction::Unhandled,
+            (KeyCode::Char(character), false) => {
+                self.line.insert(self.cursor, character);
+                self.cursor += 1;
+            }
+            (KeyCode::Left, _) => self.cursor = self.cursor.saturating_sub(1),
+            (KeyCode::Right, _) => self.cursor = (self.cursor + 1).min(self.line.len()),
+            (KeyCode::Backspace, _) => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                }
+            }
+            (KeyCode::Delete, _) => {
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                }
+            }
+            (KeyCode::Up, _) => self.browse_history(true),
+            (KeyCode::Down, _) => self.browse_history(false),
+            (KeyCode::Enter, _) => {
+                return LineEditorAction::Send(self.take_line(tx_eol, tx_encoding))
+            }
+            _ => return LineEditorAction::Unhandled,
+        }
+        LineEditorAction::Edited
+    }
+
+    /// Insert pasted text at the cursor, returning the bytes to send for each line it completes
+    /// as if Enter was pressed. What follows the last line break is left to edit.
+    pub fn paste(&mut self, text: &str, tx_eol: LineEnding, tx_encoding: Encoding) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (index, line) in paste::lines(text).enumerate() {
+            if index > 0 {
+                bytes.extend(self.take_line(tx_eol, tx_encoding));
+            }
+            for character in line.chars().filter(|c| !c.is_control()) {
+                self.line.insert(self.cursor, character);
+                self.cursor += 1;
+            }
+        }
+        bytes
+    }
+
+    /// Show the previous or next history line, back to the line being typed after the newest.
+    fn browse_history(&mut self, back: bool) {
+        let index = match (self.history_index, back) {
+            (None, true) if !self.history.is_empty() => {
+                self.draft = self.line.clone();
+                Some(self.history.len() - 1)
+            }
+            (None, _) => return,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < self.history.len() => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.line = match index {
+            Some(index) => self.history[index].chars().collect(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.history_index = index;
+        self.cursor = self.line.len();
+    }
+
+    /// Clear the line, adding it to the history, and return the bytes to send for it.
+    fn take_line(&mut self, tx_eol: LineEnding, tx_encoding: Encoding) -> Vec<u8> {
+        let line: String = self.line.drain(..).collect();
+        self.cursor = 0;
+        self.history_index = None;
+        self.draft.clear();
+
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+            if let Some(file) = &mut self.file {
+                if let Err(error) = writeln!(file, "{}", line) {
+                    log_to_ui!("History file error '{}', history is no longer saved", error);
+                    self.file = None;
+                }
+            }
+        }
+
+        let mut bytes = tx_encoding.encode(&line);
+        bytes.extend_from_slice(tx_eol.bytes());
+        bytes
+    }
+
+    /// The line's first shown character, scrolling sideways to keep the cursor in view.
+    fn first_shown(&self) -> usize {
+        let (cols, _) = size().unwrap();
+        let width = usize::from(cols).saturating_sub(PROMPT.len() + 1).max(1);
+        self.cursor.saturating_sub(width)
+    }
+
+    pub fn cursor_column(&self) -> u16 {
+        (PROMPT.len() + self.cursor - self.first_shown()) as u16
+    }
+}
+
+/// Draw the edit line on its row, leaving the cursor where it was.
+pub fn draw(line_editor: &LineEditor, row: u16) {
+    let (cols, _) = size().unwrap();
+    let shown: String = line_editor.line[line_editor.first_shown()..]
+        .iter()
+        .take(usize::from(cols).saturating_sub(PROMPT.len()))
+        .collect();
+    queue!(
+        stdout(),
+        cursor::SavePosition,
+        cursor::MoveTo(0, row),
+        Clear(CurrentLine),
+        SetForegroundColor(DarkGrey),
+        Print(PROMPT),
+        SetForegroundColor(Reset),
+        Print(shown),
+        cursor::RestorePosition,
+    )
+    .unwrap();
+}
+
+/// Handle the menu command `line on` or `line off`, returning a message for the UI.
+pub fn line_command(line_editor: &mut LineEditor, argument: &str) -> String {
+    match argument {
+        "on" => line_editor.enabled = true,
+        "off" => line_editor.enabled = false,
+        "" => {}
+        _ => return String::from("Usage: line on|off"),
+    };
+    match line_editor.enabled {
+        true => String::from("Line input on, lines are sent on Enter"),
+        false => String::from("Line input off, keys are sent as they are typed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pasted_lines_are_sent_and_the_rest_is_left_to_edit() {
+        let mut line_editor = LineEditor::new(true);
+        let bytes = line_editor.paste("one\r\ntwo\nthr", LineEnding::Cr, Encoding::Utf8);
+        assert_eq!(bytes, b"one\rtwo\r");
+        assert_eq!(line_editor.line.iter().collect::<String>(), "thr");
+        assert_eq!(line_editor.history, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn paste_goes_in_at_the_cursor() {
+        let mut line_editor = LineEditor::new(true);
+        line_editor.paste("ad", LineEnding::Cr, Encoding::Utf8);
+        line_editor.cursor = 1;
+        assert!(line_editor
+            .paste("b\tc", LineEnding::Cr, Encoding::Utf8)
+            .is_empty());
+        assert_eq!(line_editor.line.iter().collect::<String>(), "abcd");
+        assert_eq!(line_editor.cursor, 3);
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt::{Display, Formatter, Result};
+
+use crate::rx_display::RxDisplay;
+
+/// What ends a line: on RX what the device sends, on TX what Enter sends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Cr,
+    Lf,
+    CrLf,
+    /// Any of CR, LF, or CRLF, only for RX
+    Auto,
+}
+
+impl LineEnding {
+    pub fn parse(name: &str) -> Option<LineEnding> {
+        match name.to_lowercase().as_str() {
+            "cr" => Some(LineEnding::Cr),
+            "lf" => Some(LineEnding::Lf),
+            "crlf" => Some(LineEnding::CrLf),
+            "auto" => Some(LineEnding::Auto),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> &'static [u8] {
+        match self {
+            LineEnding::Cr => b"\r",
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf | LineEnding::Auto => b"\r\n",
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let name = match self {
+            LineEnding::Cr => "cr",
+            LineEnding::Lf => "lf",
+            LineEnding::CrLf => "crlf",
+            LineEnding::Auto => "auto",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Handle the menu command `eol rx cr|lf|crlf|auto` or `eol tx cr|lf|crlf`, returning a message
+/// for the UI. `eol` alone shows both.
+pub fn eol_command(rx_display: &mut RxDisplay, tx_eol: &mut LineEnding, argument: &str) -> String {
+    let usage = String::from("Usage: eol rx cr|lf|crlf|auto | eol tx cr|lf|crlf");
+    if argument.is_empty() {
+        return format!(
+            "RX line ending {}, TX line ending {}",
+            rx_display.rx_eol, tx_eol
+        );
+    }
+    let (direction, name) = match argument.split_once(' ') {
+        Some((direction, name)) => (direction, name.trim()),
+        None => return usage,
+    };
+    match (direction, LineEnding::parse(name)) {
+        ("rx", Some(line_ending)) => {
+            rx_display.rx_eol = line_ending;
+            format!("RX line ending {}", line_ending)
+        }
+        ("tx", Some(line_ending)) if line_ending != LineEnding::Auto => {
+            *tx_eol = line_ending;
+            format!("TX line ending {}", line_ending)
+        }
+        _ => usage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::rx_display::ControlChars;
+
+    fn rx_display() -> RxDisplay {
+        RxDisplay::new(
+            false,
+            Encoding::Utf8,
+            LineEnding::Lf,
+            ControlChars::Raw,
+            vec![],
+        )
+    }
+
+    #[test]
+    fn shows_both_line_endings() {
+        let mut rx_display = rx_display();
+        let mut tx_eol = LineEnding::Cr;
+        assert_eq!(
+            eol_command(&mut rx_display, &mut tx_eol, ""),
+            "RX line ending lf, TX line ending cr"
+        );
+    }
+
+    #[test]
+    fn sets_a_line_ending() {
+        let mut rx_display = rx_display();
+        let mut tx_eol = LineEnding::Cr;
+        assert_eq!(
+            eol_command(&mut rx_display, &mut tx_eol, "rx  AUTO"),
+            "RX line ending auto"
+        );
+        assert_eq!(rx_display.rx_eol, LineEnding::Auto);
+        assert_eq!(
+            eol_command(&mut rx_display, &mut tx_eol, "tx crlf"),
+            "TX line ending crlf"
+        );
+        assert_eq!(tx_eol, LineEnding::CrLf);
+    }
+
+    #[test]
+    fn rejects_bad_arguments_and_auto_for_tx() {
+        let mut rx_display = rx_display();
+        let mut tx_eol = LineEnding::Cr;
+        for argument in ["tx auto", "rx", "rx nul", "both lf"] {
+            assert!(
+                eol_command(&mut rx_display, &mut tx_eol, argument).starts_with("Usage"),
+                "{}",
+                argument
+            );
+        }
+        assert_eq!(tx_eol, LineEnding::Cr);
+        assert_eq!(rx_display.rx_eol, LineEnding::Lf);
+    }
+}
@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+use regex::Regex;
+use tokio::time::Instant;
+
+use crate::escape_parser::plain_text;
+use crate::rx_display::RxOutput;
+
+/// How long a held back line may go without more data before it is shown anyway, as a prompt
+/// never gets its newline.
+const HELD_LINE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Hides received lines from the display by regex. While a filter is set each line is held
+/// back until it is complete, to know whether it matches, or until the device goes quiet.
+pub struct LineFilter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+    /// Output of the line being received, and its text without escape sequences
+    line: Vec<RxOutput>,
+    text: String,
+    /// A line started before the filter was set, it is let through to the end
+    mid_line: bool,
+    /// When the held back line is shown if nothing more arrives
+    release_at: Option<Instant>,
+}
+
+impl LineFilter {
+    pub fn new() -> LineFilter {
+        LineFilter {
+            include: None,
+            exclude: None,
+            line: Vec::new(),
+            text: String::new(),
+            mid_line: false,
+            release_at: None,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.include.is_some() || self.exclude.is_some()
+    }
+
+    /// Whether the line being received is shown as it arrives, rather than held back.
+    pub fn shows_partial_line(&self) -> bool {
+        !self.is_active() || self.mid_line
+    }
+
+    /// Whether a line passes the filter, true for every line when there is none.
+    pub fn is_shown(&self, text: &str) -> bool {
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|include| include.is_match(text));
+        let excluded = self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(text));
+        included && !excluded
+    }
+
+    /// The output to display, lines that are complete and match, or everything if there is no
+    /// filter. A held back line is released with the next output once the filter is removed.
+    pub fn apply(&mut self, outputs: Vec<RxOutput>) -> Vec<RxOutput> {
+        let mut shown = Vec::new();
+        if !self.is_active() && !self.line.is_empty() {
+            shown.append(&mut self.release());
+        }
+
+        for output in outputs {
+            if !self.is_active() || self.mid_line {
+                self.mid_line = !matches!(output, RxOutput::Newline);
+                shown.push(output);
+                continue;
+            }
+            match output {
+                RxOutput::Newline => {
+                    if self.is_shown(&self.text) {
+                        shown.append(&mut self.line);
+                        shown.push(RxOutput::Newline);
+                    }
+                    self.line.clear();
+                    self.text.clear();
+                }
+                RxOutput::Print(ref text) => {
+                    self.text.push_str(&plain_text(text));
+                    self.line.push(output);
+                }
+                _ => self.line.push(output),
+            }
+        }
+        self.release_at = match self.line.is_empty() {
+            true => None,
+            false => Some(Instant::now() + HELD_LINE_TIMEOUT),
+        };
+        shown
+    }
+
+    /// When the held back line is to be shown, as nothing more has arrived.
+    pub fn release_deadline(&self) -> Option<Instant> {
+        self.release_at
+    }
+
+    /// The held back line if what has arrived of it passes the filter, which is then let
+    /// through to its end. Otherwise it stays held until it is complete.
+    pub fn release(&mut self) -> Vec<RxOutput> {
+        self.release_at = None;
+        if !self.is_shown(&self.text) {
+            return Vec::new();
+        }
+        self.mid_line = !self.line.is_empty();
+        self.text.clear();
+        std::mem::take(&mut self.line)
+    }
+}
+
+fn filter_status(line_filter: &LineFilter) -> String {
+    match (&line_filter.include, &line_filter.exclude) {
+        (None, None) => String::from("No filter"),
+        (Some(include), None) => format!("Showing lines matching {}", include),
+        (None, Some(exclude)) => format!("Hiding lines matching {}", exclude),
+        (Some(include), Some(exclude)) => format!(
+            "Showing lines matching {} except those matching {}",
+            include, exclude
+        ),
+    }
+}
+
+/// Handle the menu command `filter <regex>` or `filter off`, returning a message for the UI.
+pub fn filter_command(line_filter: &mut LineFilter, argument: &str) -> String {
+    match argument {
+        "" => {}
+        "off" => {
+            line_filter.include = None;
+            line_filter.exclude = None;
+        }
+        pattern => match Regex::new(pattern) {
+            Ok(include) => line_filter.include = Some(include),
+            Err(error) => return format!("Invalid pattern: {}", error),
+        },
+    };
+    filter_status(line_filter)
+}
+
+/// Handle the menu command `exclude <regex>`, returning a message for the UI.
+pub fn exclude_command(line_filter: &mut LineFilter, argument: &str) -> String {
+    match argument {
+        "" => return String::from("Usage: exclude <regex>"),
+        pattern => match Regex::new(pattern) {
+            Ok(exclude) => line_filter.exclude = Some(exclude),
+            Err(error) => return format!("Invalid pattern: {}", error),
+        },
+    };
+    filter_status(line_filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printed(outputs: Vec<RxOutput>) -> String {
+        outputs
+            .into_iter()
+            .map(|output| match output {
+                RxOutput::Print(text) => text,
+                RxOutput::Newline => String::from("\n"),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    fn apply(line_filter: &mut LineFilter, text: &str) -> String {
+        let outputs = text
+            .split_inclusive('\n')
+            .flat_map(|line| match line.strip_suffix('\n') {
+                Some(line) => vec![RxOutput::Print(String::from(line)), RxOutput::Newline],
+                None => vec![RxOutput::Print(String::from(line))],
+            })
+            .collect();
+        printed(line_filter.apply(outputs))
+    }
+
+    #[test]
+    fn shows_everything_without_a_filter() {
+        let mut line_filter = LineFilter::new();
+        assert_eq!(apply(&mut line_filter, "a\nb"), "a\nb");
+        assert!(line_filter.release_deadline().is_none());
+    }
+
+    #[test]
+    fn holds_lines_until_they_are_complete() {
+        let mut line_filter = LineFilter::new();
+        filter_command(&mut line_filter, "err");
+        exclude_command(&mut line_filter, "debug");
+        assert_eq!(apply(&mut line_filter, "ok\nerror "), "");
+        assert!(line_filter.release_deadline().is_some());
+        assert_eq!(apply(&mut line_filter, "1\nerror debug\n"), "error 1\n");
+        assert!(line_filter.release_deadline().is_none());
+    }
+
+    #[test]
+    fn releases_a_partial_line_that_passes() {
+        let mut line_filter = LineFilter::new();
+        filter_command(&mut line_filter, "login");
+        assert_eq!(apply(&mut line_filter, "login: "), "");
+        assert_eq!(printed(line_filter.release()), "login: ");
+        // the rest of the line is let through, then lines are filtered again
+        assert_eq!(apply(&mut line_filter, "root\nok\n"), "root\n");
+    }
+
+    #[test]
+    fn keeps_holding_a_partial_line_that_does_not_pass() {
+        let mut line_filter = LineFilter::new();
+        filter_command(&mut line_filter, "error");
+        assert_eq!(apply(&mut line_filter, "$ "), "");
+        assert_eq!(printed(line_filter.release()), "");
+        assert!(line_filter.release_deadline().is_none());
+        assert_eq!(apply(&mut line_filter, "ls\n"), "");
+
+        filter_command(&mut line_filter, "off");
+        exclude_command(&mut line_filter, "secret");
+        assert_eq!(apply(&mut line_filter, "secret "), "");
+        assert_eq!(printed(line_filter.release()), "");
+        assert_eq!(apply(&mut line_filter, "key\nerror\n"), "error\n");
+    }
+
+    #[test]
+    fn shows_the_held_line_once_the_filter_is_removed() {
+        let mut line_filter = LineFilter::new();
+        filter_command(&mut line_filter, "error");
+        assert_eq!(apply(&mut line_filter, "$ "), "");
+        filter_command(&mut line_filter, "off");
+        assert_eq!(apply(&mut line_filter, "ls\n"), "$ ls\n");
+    }
+}
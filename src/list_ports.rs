@@ -2,6 +2,43 @@
 
 use serialport::{available_ports, SerialPortType};
 
+/// The serial ports found, with what is known about USB ports.
+pub fn port_descriptions() -> Vec<(String, String)> {
+    match available_ports() {
+        Ok(ports) => ports
+            .into_iter()
+            .map(|port| {
+                let about = match port.port_type {
+                    SerialPortType::UsbPort(info) => format!(
+                        "USB {:04x}:{:04x} {} {}",
+                        info.vid,
+                        info.pid,
+                        info.manufacturer.unwrap_or_default(),
+                        info.product.unwrap_or_default()
+                    ),
+                    SerialPortType::BluetoothPort => String::from("Bluetooth"),
+                    SerialPortType::PciPort => String::from("PCI"),
+                    SerialPortType::Unknown => String::new(),
+                };
+                (port.port_name, about)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Handle the menu command `ports`, returning a message for the UI.
+pub fn ports_command() -> String {
+    let ports = port_descriptions();
+    match ports.is_empty() {
+        true => String::from("No ports found"),
+        false => {
+            let names: Vec<String> = ports.into_iter().map(|(name, _)| name).collect();
+            format!("Ports: {}", names.join(", "))
+        }
+    }
+}
+
 pub fn list_ports() {
     match available_ports() {
         Ok(ports) => {
@@ -1,6 +1,3 @@
-use std::io::stdout;
-
-use chrono;
 use crossterm::{
     cursor::{Hide, Show},
     queue,
@@ -10,6 +7,9 @@ use crossterm::{
     },
 };
 
+use crate::cast::stdout;
+use crate::constants::TIMESTAMP_FORMAT;
+
 macro_rules! log_to_ui {
     ( $ ( $arg:tt ) * ) => {{
         print_log_to_stdout(format!($($arg)*));
@@ -26,7 +26,7 @@ pub fn print_log_to_stdout(msg: String) {
         SetForegroundColor(White),
         Print(format!(
             "\r\n[{}] ",
-            chrono::offset::Local::now().format("%H:%M:%S%.3f")
+            chrono::offset::Local::now().format(TIMESTAMP_FORMAT)
         )),
         Print(msg),
         SetBackgroundColor(Reset),
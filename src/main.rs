@@ -1,47 +1,107 @@
 use const_format::concatcp;
-use futures::{future::FutureExt, pin_mut, select, stream::StreamExt};
+use futures::{
+    future::{self, FutureExt},
+    pin_mut, select,
+    stream::StreamExt,
+};
 use std::{
     format,
     io::{
-        self,
         ErrorKind::{PermissionDenied, TimedOut, WouldBlock},
         Write,
     },
+    path::PathBuf,
+    process,
+    time::Duration,
 };
 
-use clap::{error::ContextKind::InvalidArg, error::ContextValue, Parser};
+use clap::{error::ContextKind::InvalidArg, error::ContextValue, ArgGroup, Parser, Subcommand};
 use crossterm::{
     cursor,
-    event::EventStream,
+    event::{DisableBracketedPaste, EnableBracketedPaste, Event as CrosstermEvent, EventStream},
     execute, queue,
     style::Print,
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType::CurrentLine},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, size, Clear,
+        ClearType::{CurrentLine, UntilNewLine},
+    },
 };
-use tokio::io::AsyncReadExt;
+use regex::Regex;
+use tokio::{io::AsyncReadExt, time::MissedTickBehavior};
 use tokio_serial::{DataBits, FlowControl, Parity, SerialStream, StopBits};
 
 mod arg_helpers;
+mod capture;
+mod cast;
 mod constants;
+mod echo;
+mod encoding;
+mod escape_parser;
+mod event_log;
+mod highlight;
+mod history;
 mod keyboard_input;
+mod line_editor;
+mod line_ending;
+mod line_filter;
 mod list_ports;
 mod log_to_ui;
 mod menu;
+mod pager;
+mod paste;
+mod pcap;
+mod rotation;
+mod rx_display;
+mod scroll_region;
 mod serial_connection;
+mod session_log;
+mod stats;
+mod status_bar;
+mod tui;
+mod tx_pane;
 
 use crate::arg_helpers::{
-    valid_baud, valid_data_bits, valid_flow_control, valid_parity, valid_stop_bits, CLIDisplay,
+    valid_baud, valid_chunk_size, valid_control_chars, valid_data_bits, valid_encoding,
+    valid_escape_key, valid_flow_control, valid_highlight, valid_interval, valid_parity,
+    valid_rx_eol, valid_size, valid_speed, valid_stop_bits, valid_tx_eol, CLIDisplay,
 };
+use crate::capture::{Capture, Direction, Recorder};
 use crate::constants::{ABOUT, HELP, LONG_VERSION};
-use crate::keyboard_input::{handle_keypress_event, KeyboardInputAction};
+use crate::echo::Echo;
+use crate::encoding::Encoding;
+use crate::event_log::{log_event, Event};
+use crate::highlight::HighlightRule;
+use crate::history::{DeferredOutput, History};
+use crate::keyboard_input::{escape_key, handle_keypress_event, KeyboardInputAction};
+use crate::line_editor::{LineEditor, LineEditorAction};
+use crate::line_ending::LineEnding;
+use crate::line_filter::LineFilter;
 use crate::list_ports::list_ports;
 use crate::log_to_ui::{log_to_ui, print_log_to_stdout};
+use crate::paste::Paste;
+use crate::pcap::Pcap;
+use crate::rotation::{Interval, Rotation};
+use crate::rx_display::{ControlChars, RxDisplay, RxOutput};
 use crate::serial_connection::wait_for_serial_port;
+use crate::session_log::SessionLog;
+use crate::stats::Stats;
+use crate::status_bar::StatusBar;
+use crate::tui::Popup;
+use crate::tx_pane::TxPane;
 
 #[derive(Parser, Debug)]
 #[command(author, version, long_version = LONG_VERSION, about = ABOUT, long_about = concatcp!(ABOUT, "\n\n", HELP))]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(group(ArgGroup::new("rotation").args(["log_max_size", "log_rotate"]).multiple(true)))]
 pub struct Args {
-    #[arg(help = "Serial port, e.g. 'COM1' or '/dev/ttyUSB0'. Use '?' to list")]
-    port: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        required_unless_present = "tui",
+        help = "Serial port, e.g. 'COM1' or '/dev/ttyUSB0'. Use '?' to list, or with --tui to select"
+    )]
+    port: Option<String>,
 
     #[arg(short, long, default_value_t = 115_200, value_parser = valid_baud)]
     baud: u32,
@@ -79,10 +139,219 @@ pub struct Args {
         help = "1 or 2"
     )]
     stop_bits: CLIDisplay<StopBits>,
+
+    #[arg(long, help = "Append all received data to a log file")]
+    log: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_parser = valid_size,
+        help = "Start a new log file when it reaches a size, e.g. 50M"
+    )]
+    log_max_size: Option<u64>,
+
+    #[arg(
+        long,
+        value_parser = valid_interval,
+        help = "Start a new log file every hour or day: hourly or daily"
+    )]
+    log_rotate: Option<Interval>,
+
+    #[arg(
+        long,
+        requires = "rotation",
+        help = "Number of rotated log files to keep, oldest are deleted"
+    )]
+    log_keep: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = 10_000,
+        help = "Number of received lines kept for the pager"
+    )]
+    scrollback: usize,
+
+    #[arg(long, help = "Prefix each received line with a timestamp")]
+    timestamps: bool,
+
+    #[arg(long, help = "Show connection details in a status bar at the bottom")]
+    status_bar: bool,
+
+    #[arg(long, help = "Show sent data in a pane below the received data")]
+    split: bool,
+
+    #[arg(
+        long,
+        help = "Show what is typed among the received data, in its own color"
+    )]
+    echo: bool,
+
+    #[arg(
+        long,
+        help = "Edit each line before sending it on Enter, with Up and Down for history"
+    )]
+    line_input: bool,
+
+    #[arg(long, help = "Keep the line input history in a file between sessions")]
+    history_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = CLIDisplay { name: String::from("ctrl-t"), value: 0x14 },
+        value_parser = valid_escape_key,
+        help = "Key for the menu, e.g. ctrl-] or ^a, pressed twice to send it"
+    )]
+    escape_key: CLIDisplay<u8>,
+
+    #[arg(
+        long,
+        help = "Use the whole terminal with a fixed command line, status bar, and popups"
+    )]
+    tui: bool,
+
+    #[arg(
+        long,
+        default_value_t = Encoding::Utf8,
+        value_parser = valid_encoding,
+        help = "utf-8, latin1, cp437, or ascii"
+    )]
+    encoding: Encoding,
+
+    #[arg(
+        long,
+        default_value_t = Encoding::Utf8,
+        value_parser = valid_encoding,
+        help = "Encoding of typed characters: utf-8, latin1, cp437, or ascii"
+    )]
+    tx_encoding: Encoding,
+
+    #[arg(
+        long,
+        default_value_t = LineEnding::CrLf,
+        value_parser = valid_rx_eol,
+        help = "Line ending sent by the device: cr, lf, crlf, or auto"
+    )]
+    rx_eol: LineEnding,
+
+    #[arg(
+        long,
+        default_value_t = LineEnding::Cr,
+        value_parser = valid_tx_eol,
+        help = "Line ending sent for Enter: cr, lf, or crlf"
+    )]
+    tx_eol: LineEnding,
+
+    #[arg(
+        long,
+        default_value_t = 64,
+        value_parser = valid_chunk_size,
+        help = "Bytes of pasted text sent at a time"
+    )]
+    paste_chunk_size: usize,
+
+    #[arg(
+        long,
+        default_value_t = 10,
+        help = "Milliseconds between chunks of pasted text"
+    )]
+    paste_delay: u64,
+
+    #[arg(
+        long,
+        default_value_t = ControlChars::Raw,
+        value_parser = valid_control_chars,
+        help = "Show control characters as raw, caret (^C), pictures (␃), or hex (<0x03>)"
+    )]
+    control_chars: ControlChars,
+
+    #[arg(
+        long,
+        value_parser = valid_highlight,
+        help = "Color received text matching a regex, e.g. 'ERR|FAIL=red' or 'WARN=black:yellow'"
+    )]
+    highlight: Vec<HighlightRule>,
+
+    #[arg(long, help = "Read highlight rules from a file, one per line")]
+    highlight_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Record received and sent data with timestamps for replay"
+    )]
+    capture: Option<PathBuf>,
+
+    #[arg(long, help = "Record the terminal as an asciinema v2 .cast file")]
+    record_cast: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Write received and sent data to a pcapng file for Wireshark"
+    )]
+    pcap: Option<PathBuf>,
+
+    #[arg(long, help = "Write connection events to a JSON-lines file")]
+    event_log: Option<PathBuf>,
+}
+
+impl Args {
+    /// The port is always given unless running a subcommand
+    fn port(&self) -> &str {
+        self.port.as_deref().unwrap_or_default()
+    }
+
+    /// Applies to every file written, --log, --pcap, --capture and --record-cast as well as those
+    /// started from the menu
+    fn rotation(&self) -> Rotation {
+        Rotation {
+            max_size: self.log_max_size,
+            interval: self.log_rotate,
+            keep: self.log_keep,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "Play back a file recorded with --capture")]
+    Replay {
+        file: PathBuf,
+
+        #[arg(
+            long,
+            default_value_t = 1.0,
+            value_parser = valid_speed,
+            help = "Playback speed factor, or 'max' for no delays"
+        )]
+        speed: f64,
+
+        #[arg(
+            long,
+            default_value_t = Encoding::Utf8,
+            value_parser = valid_encoding,
+            help = "utf-8, latin1, cp437, or ascii"
+        )]
+        encoding: Encoding,
+
+        #[arg(
+            long,
+            default_value_t = LineEnding::CrLf,
+            value_parser = valid_rx_eol,
+            help = "Line ending sent by the device: cr, lf, crlf, or auto"
+        )]
+        rx_eol: LineEnding,
+
+        #[arg(
+            long,
+            default_value_t = ControlChars::Raw,
+            value_parser = valid_control_chars,
+            help = "Show control characters as raw, caret (^C), pictures (␃), or hex (<0x03>)"
+        )]
+        control_chars: ControlChars,
+    },
 }
 
 fn main() {
-    let args = match Args::try_parse() {
+    let mut args = match Args::try_parse() {
         Ok(args) => args,
         Err(error) => {
             // if the <PORT> argument is omitted, list ports but still exit with error
@@ -98,19 +367,124 @@ fn main() {
         }
     };
 
-    if args.port == "?" {
+    if let Some(Command::Replay {
+        file,
+        speed,
+        encoding,
+        rx_eol,
+        control_chars,
+    }) = &args.command
+    {
+        if let Err(error) = capture::replay(file, *speed, *encoding, *rx_eol, *control_chars) {
+            eprintln!("Unable to replay {}: {}", file.display(), error);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if args.tui && args.port.as_deref().is_none_or(|port| port == "?") {
+        enable_raw_mode().unwrap();
+        let port = tui::select_port();
+        disable_raw_mode().unwrap();
+        match port {
+            Some(port) => args.port = Some(port),
+            None => {
+                eprintln!("No port selected");
+                process::exit(1);
+            }
+        }
+    }
+
+    if args.port() == "?" {
         list_ports();
         return;
     }
 
+    let mut highlight_rules = args.highlight.clone();
+    if let Some(path) = &args.highlight_file {
+        match highlight::load_rules(path) {
+            Ok(rules) => highlight_rules.extend(rules),
+            Err(error) => {
+                eprintln!(
+                    "Unable to read highlight rules from {}: {}",
+                    path.display(),
+                    error
+                );
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut session_log = SessionLog::new(args.rotation());
+    if let Some(path) = &args.log {
+        if let Err(error) = session_log.start(path) {
+            eprintln!("Unable to log to {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+
+    let capture = match &args.capture {
+        Some(path) => match Capture::create(path, args.rotation()) {
+            Ok(capture) => Some(capture),
+            Err(error) => {
+                eprintln!("Unable to capture to {}: {}", path.display(), error);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let pcap = match &args.pcap {
+        Some(path) => match Pcap::create(path, args.port(), args.rotation()) {
+            Ok(pcap) => Some(pcap),
+            Err(error) => {
+                eprintln!("Unable to write pcap to {}: {}", path.display(), error);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if let Some(path) = &args.event_log {
+        if let Err(error) = event_log::start(path, &args, args.rotation()) {
+            eprintln!("Unable to log events to {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+
+    let mut line_editor = LineEditor::new(args.line_input);
+    if let Some(path) = &args.history_file {
+        if let Err(error) = line_editor.load_history(path) {
+            eprintln!("Unable to use history file {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+
+    if let Some(path) = &args.record_cast {
+        if let Err(error) = cast::start(path, size().unwrap(), args.rotation()) {
+            eprintln!("Unable to record to {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+
+    keyboard_input::set_escape_key(args.escape_key.value);
     enable_raw_mode().unwrap();
+    execute!(cast::stdout(), EnableBracketedPaste).unwrap();
 
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .unwrap()
-        .block_on(io_tasks(args));
+        .block_on(io_tasks(
+            args,
+            session_log,
+            capture,
+            pcap,
+            highlight_rules,
+            line_editor,
+        ));
 
+    execute!(cast::stdout(), DisableBracketedPaste).unwrap();
     disable_raw_mode().unwrap();
 }
 
@@ -120,10 +494,396 @@ enum EventType {
     Initial,
 }
 
-async fn io_tasks(args: Args) {
+/// Add bytes to the recorder, if there is one, abandoning it on error.
+fn record<T: Recorder>(recorder: &mut Option<T>, name: &str, direction: Direction, bytes: &[u8]) {
+    if let Some(active_recorder) = recorder {
+        if let Err(error) = active_recorder.record(direction, bytes) {
+            log_to_ui!("{} error '{}', {} stopped", name, error, name);
+            *recorder = None;
+        }
+    }
+}
+
+/// Wait for the deadline, or forever if there is none.
+async fn wait_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => future::pending().await,
+    }
+}
+
+/// The next tick of the interval, or never if it is not `armed`.
+async fn tick_if(interval: &mut tokio::time::Interval, armed: bool) {
+    match armed {
+        true => {
+            interval.tick().await;
+        }
+        false => future::pending().await,
+    }
+}
+
+/// Write bytes to the port and record them, returning how many the port took, or None after an
+/// error.
+fn write_tx(
+    serial_conn: &mut SerialStream,
+    bytes: &[u8],
+    stats: &mut Stats,
+    tx_pane: &mut Option<TxPane>,
+    capture: &mut Option<Capture>,
+    pcap: &mut Option<Pcap>,
+) -> Option<usize> {
+    match serial_conn.write(bytes) {
+        Ok(written) => {
+            stats.tx(written as u64);
+            if let Some(tx_pane) = tx_pane {
+                tx_pane.push(&bytes[..written]);
+            }
+            record(capture, "Capture", Direction::Tx, &bytes[..written]);
+            record(pcap, "pcap", Direction::Tx, &bytes[..written]);
+            Some(written)
+        }
+        Err(error) => match error.kind() {
+            WouldBlock => Some(0),
+            _ => {
+                log_to_ui!("Serial TX Error: {:?}", error);
+                log_event(Event::TxError(&error));
+                None
+            }
+        },
+    }
+}
+
+/// Print rendered RX output at the RX cursor, moving the open menu down for each newline unless
+/// it has a `fixed_menu` row. Clearing the screen and moving the cursor are kept to the top
+/// `rx_rows` rows, above the menu when it is not fixed. The device's saved cursor is kept in
+/// `saved_rx_cursor_position`, as the terminal's own saved cursor is used for the menu.
+///
+/// Returns true if the RX cursor was last moved by printing, so it is to be read back after the
+/// flush, rather than set from the menu row.
+fn print_rx_output(
+    outputs: Vec<RxOutput>,
+    menu_state: menu::MenuState,
+    serial_rx_cursor_position: (u16, u16),
+    saved_rx_cursor_position: (u16, u16),
+    fixed_menu: bool,
+    rx_rows: u16,
+) -> (menu::MenuState, (u16, u16), (u16, u16), bool) {
+    let mut stdout = cast::stdout();
+    let mut menu_state = menu_state;
+    let mut serial_rx_cursor_position = serial_rx_cursor_position;
+    let mut saved_rx_cursor_position = saved_rx_cursor_position;
+    let mut printed = false;
+    // the rows the device may clear and move around in, above the menu when it moves down
+    let device_rows = |menu_state: &menu::MenuState| match menu_state.is_open && !fixed_menu {
+        true => menu_state.cursor_position.1,
+        false => rx_rows,
+    };
+
+    // the rx cursor position is only refreshed after the flush, so move there once
+    let mut at_rx_cursor = false;
+    for output in outputs {
+        if !at_rx_cursor {
+            let (col, row) = serial_rx_cursor_position;
+            queue!(stdout, cursor::MoveTo(col, row)).unwrap();
+        }
+        match output {
+            RxOutput::Newline if menu_state.is_open && !fixed_menu => {
+                // move to the menu line and clear it
+                queue!(
+                    stdout,
+                    cursor::MoveTo(0, menu_state.cursor_position.1),
+                    Clear(CurrentLine),
+                )
+                .unwrap();
+
+                // add the menu back
+                menu_state = menu::newline(menu_state);
+
+                // manually set the serial rx cursor up 1 row from the menu
+                serial_rx_cursor_position = (0, menu_state.cursor_position.1 - 1);
+                printed = false;
+                at_rx_cursor = false;
+            }
+            RxOutput::Newline => {
+                queue!(stdout, Print('\n')).unwrap();
+                printed = true;
+                at_rx_cursor = true;
+            }
+            RxOutput::Print(text) => {
+                queue!(stdout, Print(text)).unwrap();
+                printed = true;
+                at_rx_cursor = true;
+            }
+            RxOutput::ClearScreen => {
+                for row in 0..rx_rows {
+                    queue!(stdout, cursor::MoveTo(0, row), Clear(CurrentLine)).unwrap();
+                }
+                if menu_state.is_open && !fixed_menu {
+                    menu_state = menu::open_at_row(menu_state, 1.min(rx_rows.saturating_sub(1)));
+                }
+                serial_rx_cursor_position = (0, 0);
+                printed = false;
+                at_rx_cursor = false;
+            }
+            RxOutput::ClearBelow => {
+                if printed {
+                    stdout.flush().unwrap();
+                    serial_rx_cursor_position = cursor::position().unwrap();
+                }
+                let (col, row) = serial_rx_cursor_position;
+                queue!(stdout, cursor::MoveTo(col, row), Clear(UntilNewLine)).unwrap();
+                for row in row + 1..device_rows(&menu_state) {
+                    queue!(stdout, cursor::MoveTo(0, row), Clear(CurrentLine)).unwrap();
+                }
+                printed = false;
+                at_rx_cursor = false;
+            }
+            RxOutput::MoveTo(row, col) => {
+                let (cols, _) = size().unwrap();
+                serial_rx_cursor_position = (
+                    col.min(cols.saturating_sub(1)),
+                    row.min(device_rows(&menu_state).saturating_sub(1)),
+                );
+                printed = false;
+                at_rx_cursor = false;
+            }
+            RxOutput::SaveCursor => {
+                if printed {
+                    stdout.flush().unwrap();
+                    serial_rx_cursor_position = cursor::position().unwrap();
+                }
+                saved_rx_cursor_position = serial_rx_cursor_position;
+                printed = false;
+                at_rx_cursor = false;
+            }
+            RxOutput::RestoreCursor => {
+                // the rows may have changed since, e.g. for the menu moving down
+                let (cols, _) = size().unwrap();
+                let (col, row) = saved_rx_cursor_position;
+                serial_rx_cursor_position = (
+                    col.min(cols.saturating_sub(1)),
+                    row.min(device_rows(&menu_state).saturating_sub(1)),
+                );
+                printed = false;
+                at_rx_cursor = false;
+            }
+            // answered by the caller, once the cursor is known
+            RxOutput::CursorPositionReport => {}
+        }
+    }
+    (
+        menu_state,
+        serial_rx_cursor_position,
+        saved_rx_cursor_position,
+        printed,
+    )
+}
+
+/// Redraw the status bar, if there is one.
+fn draw_status_bar(
+    status_bar: &Option<StatusBar>,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    session_log: &SessionLog,
+) {
+    if let Some(status_bar) = status_bar {
+        let log_path = match session_log.is_active() {
+            true => session_log
+                .path
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            false => None,
+        };
+        status_bar::draw(status_bar, rx_bytes, tx_bytes, log_path);
+    }
+}
+
+/// Whether a row is kept above the status bar for the TUI command line or the edit line.
+fn has_command_row(args: &Args, line_editor: &LineEditor) -> bool {
+    args.tui || line_editor.enabled
+}
+
+/// The row of the TUI command line or the edit line, just above the status bar.
+fn command_row(status_bar: &Option<StatusBar>) -> u16 {
+    let (_, rows) = size().unwrap();
+    rows.saturating_sub(1 + u16::from(status_bar.is_some()))
+}
+
+/// Rows below the TX pane, the status bar and the command row.
+fn rows_below_tx_pane(status_bar: &Option<StatusBar>, command_row: bool) -> u16 {
+    u16::from(status_bar.is_some()) + u16::from(command_row)
+}
+
+/// Redraw the TX pane, if there is one.
+fn draw_tx_pane(tx_pane: &Option<TxPane>, status_bar: &Option<StatusBar>, command_row: bool) {
+    if let Some(tx_pane) = tx_pane {
+        tx_pane::draw(tx_pane, rows_below_tx_pane(status_bar, command_row));
+    }
+}
+
+/// Redraw the edit line, if line input is on.
+fn draw_edit_line(line_editor: &LineEditor, status_bar: &Option<StatusBar>) {
+    if line_editor.enabled {
+        line_editor::draw(line_editor, command_row(status_bar));
+    }
+}
+
+/// Rows kept below the scrolling RX area for the TX pane, status bar and command row.
+fn reserved_rows(
+    status_bar: &Option<StatusBar>,
+    tx_pane: &Option<TxPane>,
+    command_row: bool,
+) -> u16 {
+    let tx_pane_rows = match tx_pane {
+        Some(_) => tx_pane::rows(),
+        None => 0,
+    };
+    tx_pane_rows + rows_below_tx_pane(status_bar, command_row)
+}
+
+/// Rows above the reserved rows, the TUI output area or where the stats view goes.
+fn output_rows(status_bar: &Option<StatusBar>, tx_pane: &Option<TxPane>, command_row: bool) -> u16 {
+    let (_, rows) = size().unwrap();
+    rows.saturating_sub(reserved_rows(status_bar, tx_pane, command_row))
+}
+
+/// Clear the TUI screen and draw it again from the history, after a resize or once the pager or
+/// a popup has closed, returning the new RX cursor. The status bar is left to the caller.
+#[allow(clippy::too_many_arguments)]
+fn redraw_tui(
+    history: &History,
+    line_filter: &LineFilter,
+    highlight_rules: &[HighlightRule],
+    status_bar: &Option<StatusBar>,
+    tx_pane: &Option<TxPane>,
+    menu_state: menu::MenuState,
+    popup: &Option<Popup>,
+    line_editor: &LineEditor,
+) -> (menu::MenuState, (u16, u16)) {
+    let mut stdout = cast::stdout();
+    tui::enter();
+    scroll_region::reserve_bottom_rows(reserved_rows(status_bar, tx_pane, true));
+    draw_tx_pane(tx_pane, status_bar, true);
+    let rows = output_rows(status_bar, tx_pane, true);
+    let outputs = tui::redraw_output(history, line_filter, highlight_rules, rows);
+    let (menu_state, serial_rx_cursor_position, _, printed) =
+        print_rx_output(outputs, menu_state, (0, 0), (0, 0), true, rows);
+    stdout.flush().unwrap();
+    let serial_rx_cursor_position = match printed {
+        true => cursor::position().unwrap(),
+        false => serial_rx_cursor_position,
+    };
+
+    if let Some(popup) = popup {
+        tui::draw_popup(popup, rows);
+    }
+    let menu_state = tui::draw_command_line(menu_state, command_row(status_bar));
+    if !menu_state.is_open {
+        draw_edit_line(line_editor, status_bar);
+    }
+    (menu_state, serial_rx_cursor_position)
+}
+
+/// Close the menu, bringing back what its row shows while closed.
+fn close_menu(
+    menu_state: menu::MenuState,
+    args: &Args,
+    line_editor: &LineEditor,
+    status_bar: &Option<StatusBar>,
+) -> menu::MenuState {
+    let menu_state = menu::close(menu_state);
+    if args.tui {
+        tui::draw_hint(command_row(status_bar));
+    }
+    draw_edit_line(line_editor, status_bar);
+    menu_state
+}
+
+/// Overwrite the open menu line with a log message and then reprint the menu below it. With a
+/// `fixed_menu` row the message goes at the RX cursor instead.
+fn log_to_menu(
+    msg: String,
+    menu_state: menu::MenuState,
+    serial_rx_cursor_position: (u16, u16),
+    fixed_menu: Option<u16>,
+) -> (menu::MenuState, (u16, u16)) {
+    if let Some(menu_row) = fixed_menu {
+        let (col, row) = serial_rx_cursor_position;
+        let mut stdout = cast::stdout();
+        queue!(stdout, cursor::MoveTo(col, row)).unwrap();
+        print_log_to_stdout(msg);
+        stdout.flush().unwrap();
+        let serial_rx_cursor_position = cursor::position().unwrap();
+        let menu_state = tui::draw_command_line(menu_state, menu_row);
+        return (menu_state, serial_rx_cursor_position);
+    }
+
+    queue!(cast::stdout(), cursor::MoveUp(1)).unwrap();
+    print_log_to_stdout(msg);
+
+    let menu_state = menu::newline(menu_state);
+    let serial_rx_cursor_position = (
+        serial_rx_cursor_position.0,
+        menu_state.cursor_position.1 - 1,
+    );
+    (menu_state, serial_rx_cursor_position)
+}
+
+async fn io_tasks(
+    args: Args,
+    mut session_log: SessionLog,
+    mut capture: Option<Capture>,
+    mut pcap: Option<Pcap>,
+    highlight_rules: Vec<HighlightRule>,
+    mut line_editor: LineEditor,
+) {
     let mut reader = EventStream::new();
     let mut rx_buf: [u8; 1] = [0; 1];
-    let mut stdout = io::stdout();
+    let mut stdout = cast::stdout();
+    let mut rx_display = RxDisplay::new(
+        args.timestamps,
+        args.encoding,
+        args.rx_eol,
+        args.control_chars,
+        highlight_rules.clone(),
+    );
+    let mut tx_eol = args.tx_eol;
+    let mut paste = Paste::new(
+        args.paste_chunk_size,
+        Duration::from_millis(args.paste_delay),
+    );
+    let tx_encoding = args.tx_encoding;
+    let mut echo = Echo::new(args.echo, tx_encoding);
+    let mut line_filter = LineFilter::new();
+    let mut history = History::new(args.scrollback);
+    // received while the pager is open, printed when it closes
+    let mut deferred_rx_output = DeferredOutput::new(args.scrollback);
+    let mut stats = Stats::new(&args);
+    let mut status_bar = (args.status_bar || args.tui).then(|| StatusBar::new(&args));
+    // redraws the status bar and the stats view, for the connection time and counters
+    let mut status_interval = tokio::time::interval(Duration::from_secs(1));
+    status_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut tx_pane = args.split.then(|| TxPane::new(tx_encoding));
+    // help or ports shown over the TUI output area, received data waits like it does for the pager
+    let mut popup: Option<Popup> = None;
+    // the popup is the stats view, which is refreshed every second
+    let mut showing_stats = false;
+    if args.tui {
+        tui::enter();
+    }
+    if reserved_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor)) > 0 {
+        scroll_region::reserve_bottom_rows(reserved_rows(
+            &status_bar,
+            &tx_pane,
+            has_command_row(&args, &line_editor),
+        ));
+        draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+        draw_tx_pane(&tx_pane, &status_bar, has_command_row(&args, &line_editor));
+    }
+    if args.tui {
+        tui::draw_hint(command_row(&status_bar));
+    }
+    draw_edit_line(&line_editor, &status_bar);
 
     let connect_event_fut = wait_for_serial_port(&args, None).fuse();
     pin_mut!(connect_event_fut);
@@ -138,145 +898,513 @@ async fn io_tasks(args: Args) {
 
         select! {
             event = keypress_event => {
-                match handle_keypress_event(&event) {
+                match handle_keypress_event(&event, tx_eol, tx_encoding) {
                     KeyboardInputAction::Menu => break,
-                    KeyboardInputAction::NoAction | KeyboardInputAction::Chars(_) => continue 'connection,
-                    KeyboardInputAction::KeypressError => {log_to_ui!("Keypress error"); break}
+                    KeyboardInputAction::NoAction
+                    | KeyboardInputAction::Chars(_)
+                    | KeyboardInputAction::Paste(_) => continue 'connection,
+                    KeyboardInputAction::KeypressError => {
+                        log_to_ui!("Keypress error");
+                        log_event(Event::KeypressError);
+                        break
+                    }
                 };
             },
             event = connect_event_fut => {
                 serial_conn = event;
+                stats.connected();
+                if let Some(status_bar) = &mut status_bar {
+                    status_bar.connected();
+                }
+                draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+                if let Err(error) = session_log.mark(&format!("Connected to {}", args.port())) {
+                    log_to_ui!("Log error '{}', logging stopped", error);
+                    session_log.stop();
+                }
             },
         }
 
         let mut menu_state = menu::MenuState::new(cursor::position().unwrap());
         let mut serial_rx_cursor_position: (u16, u16) = cursor::position().unwrap();
+        let mut saved_rx_cursor_position = serial_rx_cursor_position;
         let mut event_type = EventType::Initial;
+        let mut pager_state = pager::PagerState::new();
 
-        let mut ansi_sequence: Vec<u8> = Vec::with_capacity(10);
+        // show received or echoed data in its rows, after the locals above so it can update them
+        macro_rules! print_rx {
+            ($outputs:expr) => {{
+                let printed;
+                (
+                    menu_state,
+                    serial_rx_cursor_position,
+                    saved_rx_cursor_position,
+                    printed,
+                ) = print_rx_output(
+                    $outputs,
+                    menu_state,
+                    serial_rx_cursor_position,
+                    saved_rx_cursor_position,
+                    args.tui,
+                    output_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor)),
+                );
+                if printed {
+                    event_type = EventType::SerialRX;
+                }
+            }};
+        }
+
+        // reply to a menu command below the menu, or on the command row with --tui
+        macro_rules! log_to_menu {
+            ($msg:expr) => {
+                (menu_state, serial_rx_cursor_position) = log_to_menu(
+                    $msg,
+                    menu_state,
+                    serial_rx_cursor_position,
+                    args.tui.then(|| command_row(&status_bar)),
+                )
+            };
+        }
 
         'communication: loop {
             let keypress_event = reader.next().fuse();
             let serial_rx_event = serial_conn.read_exact(&mut rx_buf).fuse();
-            pin_mut!(keypress_event, serial_rx_event);
+            let status_tick =
+                tick_if(&mut status_interval, status_bar.is_some() || showing_stats).fuse();
+            let paste_chunk = wait_until(paste.deadline()).fuse();
+            let log_flush = wait_until(session_log.flush_deadline()).fuse();
+            let rx_flush = wait_until(rx_display.flush_deadline()).fuse();
+            let filter_release = wait_until(line_filter.release_deadline()).fuse();
+            pin_mut!(
+                keypress_event,
+                serial_rx_event,
+                status_tick,
+                paste_chunk,
+                log_flush,
+                rx_flush,
+                filter_release
+            );
 
             queue!(stdout, cursor::Hide).unwrap();
 
             select! {
                 event = keypress_event => {
-                    match handle_keypress_event(&event) {
-                        KeyboardInputAction::Chars(bytes) => {
-                            if menu_state.is_open {
-                                let event = event.unwrap().unwrap();
-                                menu_state = menu::handle_chars(menu_state, event);
-                                event_type = EventType::Menu;
-                                match menu_state.action {
-                                    None => {},
-                                    Some(menu::Action::Quit) => break 'connection,
-                                    Some(menu::Action::Timestamp) => {
-                                        // overwrite the menu line with the timestamp
-                                        queue!(
-                                            stdout,
-                                            cursor::MoveUp(1),
-                                        ).unwrap();
-                                        log_to_ui!(""); // blank log is just a timestamp
-
-                                        // then reprint the menu
-                                        menu_state = menu::newline(menu_state);
-                                        serial_rx_cursor_position = (
-                                            serial_rx_cursor_position.0,
-                                            menu_state.cursor_position.1 - 1);
-                                    },
-                                    Some(menu::Action::Help) => {
-                                        //TODO
-                                    }
-                                }
-                            } else {
-                                match serial_conn.write(&bytes) {
-                                    Ok(_) => {},
-                                    Err(error) => match error.kind() {
-                                        WouldBlock => {},
-                                        _ => log_to_ui!("Serial TX Error: {:?}", error)
-                                    }
-                                }
+                    let reserved = reserved_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor));
+                    if let (Some(Ok(CrosstermEvent::Resize(_, _))), true) = (&event, args.tui) {
+                        if !pager_state.is_open {
+                            (menu_state, serial_rx_cursor_position) = redraw_tui(
+                                &history, &line_filter, &highlight_rules, &status_bar, &tx_pane, menu_state, &popup, &line_editor);
+                            draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+                            event_type = EventType::Initial;
+                        }
+                    } else if let (Some(Ok(CrosstermEvent::Resize(_, _))), true) = (&event, reserved > 0) {
+                        scroll_region::reserve_bottom_rows(reserved);
+                        draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+                        draw_tx_pane(&tx_pane, &status_bar, has_command_row(&args, &line_editor));
+                        draw_edit_line(&line_editor, &status_bar);
+                    }
+                    if pager_state.is_open {
+                        pager_state = match (handle_keypress_event(&event, tx_eol, tx_encoding), event) {
+                            (KeyboardInputAction::KeypressError, _) => {
+                                log_to_ui!("Keypress error");
+                                log_event(Event::KeypressError);
+                                break 'connection
                             }
+                            (KeyboardInputAction::Menu, _) => pager::close(pager_state),
+                            (_, Some(Ok(event))) => pager::handle_event(pager_state, &history, event),
+                            (_, _) => pager_state,
+                        };
+                        if !pager_state.is_open && args.tui {
+                            // the received data is all in the history
+                            deferred_rx_output.clear();
+                            (menu_state, serial_rx_cursor_position) = redraw_tui(
+                                &history, &line_filter, &highlight_rules, &status_bar, &tx_pane, menu_state, &popup, &line_editor);
+                            draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+                            event_type = EventType::Initial;
+                        } else if !pager_state.is_open {
+                            print_rx!(deferred_rx_output.take());
                         }
-                        KeyboardInputAction::KeypressError => {
-                            log_to_ui!("Keypress error");
-                            break 'connection
+                    } else if popup.is_some() {
+                        popup = match (handle_keypress_event(&event, tx_eol, tx_encoding), event) {
+                            (KeyboardInputAction::KeypressError, _) => {
+                                log_to_ui!("Keypress error");
+                                log_event(Event::KeypressError);
+                                break 'connection
+                            }
+                            (KeyboardInputAction::Menu, _) => None,
+                            (_, Some(Ok(event))) => tui::handle_popup_event(popup.unwrap(), event),
+                            (_, _) => popup,
+                        };
+                        match &popup {
+                            Some(popup) => tui::draw_popup(popup, output_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor))),
+                            None if args.tui => {
+                                showing_stats = false;
+                                deferred_rx_output.clear();
+                                (menu_state, serial_rx_cursor_position) = redraw_tui(
+                                    &history, &line_filter, &highlight_rules, &status_bar, &tx_pane, menu_state, &popup, &line_editor);
+                                draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+                                event_type = EventType::Initial;
+                            }
+                            None => {
+                                showing_stats = false;
+                                tui::leave();
+                                print_rx!(deferred_rx_output.take());
+                            }
                         }
-                        KeyboardInputAction::Menu => {
-                            if menu_state.is_open {
-                                menu_state = menu::close(menu_state);
+                    } else {
+                        // with line input on, keys edit the line and Enter sends it
+                        let action = handle_keypress_event(&event, tx_eol, tx_encoding);
+                        let action = match (&event, &action, line_editor.enabled && !menu_state.is_open) {
+                            (
+                                Some(Ok(CrosstermEvent::Key(key))),
+                                KeyboardInputAction::Chars(_) | KeyboardInputAction::NoAction,
+                                true,
+                            ) => match line_editor.handle_key(key, tx_eol, tx_encoding) {
+                                LineEditorAction::Edited => {
+                                    draw_edit_line(&line_editor, &status_bar);
+                                    KeyboardInputAction::NoAction
+                                }
+                                LineEditorAction::Send(bytes) => {
+                                    draw_edit_line(&line_editor, &status_bar);
+                                    KeyboardInputAction::Chars(bytes)
+                                }
+                                LineEditorAction::Unhandled => action,
+                            },
+                            _ => action,
+                        };
+                        // pressing the menu key again sends it
+                        let action = match action {
+                            KeyboardInputAction::Menu if menu_state.is_open => {
+                                menu_state = close_menu(menu_state, &args, &line_editor, &status_bar);
                                 event_type = EventType::Initial;
-                            } else {
-                                menu_state = menu::newline(menu_state);
-                                serial_rx_cursor_position = (
-                                    serial_rx_cursor_position.0,
-                                    menu_state.cursor_position.1 - 1);
-                                event_type = EventType::Menu;
+                                KeyboardInputAction::Chars(vec![escape_key()])
                             }
-                        },
-                        KeyboardInputAction::NoAction => {},
-                    };
+                            action => action,
+                        };
+                        match action {
+                            KeyboardInputAction::Chars(bytes) => {
+                                if menu_state.is_open {
+                                    let event = event.unwrap().unwrap();
+                                    menu_state = menu::handle_chars(menu_state, event);
+                                    event_type = EventType::Menu;
+                                    // Esc closed the menu
+                                    if !menu_state.is_open {
+                                        menu_state = close_menu(menu_state, &args, &line_editor, &status_bar);
+                                        event_type = EventType::Initial;
+                                    }
+                                    match menu_state.action {
+                                        None => {},
+                                        Some(menu::Action::Quit) => break 'connection,
+                                        Some(menu::Action::Timestamp) => {
+                                            let msg = match menu_state.argument.as_str() {
+                                                "" => String::from(""), // blank log is just a timestamp
+                                                argument => rx_display::timestamps_command(&mut rx_display, argument),
+                                            };
+                                            log_to_menu!(msg);
+                                        },
+                                        Some(menu::Action::Help) if args.tui => {
+                                            let help = tui::help_popup();
+                                            tui::draw_popup(&help, output_rows(&status_bar, &tx_pane, true));
+                                            popup = Some(help);
+                                        }
+                                        Some(menu::Action::Help) => {
+                                            for line in tui::help_lines() {
+                                                log_to_menu!(line);
+                                            }
+                                        }
+                                        Some(menu::Action::Ports) => {
+                                            if args.tui {
+                                                let ports = tui::ports_popup();
+                                                tui::draw_popup(&ports, output_rows(&status_bar, &tx_pane, true));
+                                                popup = Some(ports);
+                                            } else {
+                                                let msg = list_ports::ports_command();
+                                                log_to_menu!(msg);
+                                            }
+                                        }
+                                        Some(menu::Action::Log) => {
+                                            let msg = session_log::log_command(&mut session_log, &menu_state.argument);
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::Pcap) => {
+                                            let msg = pcap::pcap_command(
+                                                &mut pcap, &menu_state.argument, args.port(), args.rotation());
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::View) => {
+                                            let msg = rx_display::view_command(&mut rx_display, &menu_state.argument);
+                                            // finish the line or hex row of the old view now, not on the next byte
+                                            let outputs = rx_display.take_pending();
+                                            for output in &outputs {
+                                                history.push(output);
+                                            }
+                                            let printed;
+                                            (menu_state, serial_rx_cursor_position, saved_rx_cursor_position, printed) = print_rx_output(
+                                                line_filter.apply(outputs), menu_state, serial_rx_cursor_position, saved_rx_cursor_position, args.tui,
+                                                output_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor)));
+                                            if printed {
+                                                stdout.flush().unwrap();
+                                                serial_rx_cursor_position = cursor::position().unwrap();
+                                            }
+                                            let (col, row) = menu_state.cursor_position;
+                                            queue!(stdout, cursor::MoveTo(col, row)).unwrap();
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::Eol) => {
+                                            let msg = line_ending::eol_command(
+                                                &mut rx_display, &mut tx_eol, &menu_state.argument);
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::Echo) => {
+                                            let msg = echo::echo_command(&mut echo, &menu_state.argument);
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::Line) => {
+                                            let reserved = reserved_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor));
+                                            let msg = line_editor::line_command(&mut line_editor, &menu_state.argument);
+                                            log_to_menu!(msg);
+                                            let new_reserved = reserved_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor));
+                                            if new_reserved != reserved {
+                                                // the edit line takes a row from the scrolling area or gives it back,
+                                                // which may scroll the menu and received data up
+                                                stdout.flush().unwrap();
+                                                let (_, row_before) = cursor::position().unwrap();
+                                                scroll_region::release(reserved);
+                                                scroll_region::reserve_bottom_rows(new_reserved);
+                                                stdout.flush().unwrap();
+                                                let (_, row_after) = cursor::position().unwrap();
+                                                let scrolled = row_before.saturating_sub(row_after);
+                                                menu_state.cursor_position.1 -= scrolled;
+                                                serial_rx_cursor_position.1 = serial_rx_cursor_position.1.saturating_sub(scrolled);
+                                                draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+                                                draw_tx_pane(&tx_pane, &status_bar, has_command_row(&args, &line_editor));
+                                            }
+                                            draw_edit_line(&line_editor, &status_bar);
+                                        }
+                                        Some(menu::Action::Filter) => {
+                                            let msg = line_filter::filter_command(&mut line_filter, &menu_state.argument);
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::Exclude) => {
+                                            let msg = line_filter::exclude_command(&mut line_filter, &menu_state.argument);
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::ControlChars) => {
+                                            let msg = rx_display::control_chars_command(&mut rx_display, &menu_state.argument);
+                                            log_to_menu!(msg);
+                                        }
+                                        Some(menu::Action::Stats) => {
+                                            // without --tui the view has the alternate screen, like the pager
+                                            if !args.tui {
+                                                tui::enter();
+                                            }
+                                            let view = Popup::new("Statistics", stats.lines());
+                                            tui::draw_popup(&view, output_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor)));
+                                            popup = Some(view);
+                                            showing_stats = true;
+                                        }
+                                        Some(menu::Action::Pager) => {
+                                            pager_state = pager::open(&history);
+                                        }
+                                        Some(menu::Action::Search) => {
+                                            let msg = match Regex::new(&menu_state.argument) {
+                                                Ok(search) => match pager::search(&history, search) {
+                                                    Some(searching) => {
+                                                        pager_state = searching;
+                                                        None
+                                                    }
+                                                    None => Some(format!("Pattern not found: {}", menu_state.argument)),
+                                                },
+                                                Err(error) => Some(format!("Invalid pattern: {}", error)),
+                                            };
+                                            if let Some(msg) = msg {
+                                                log_to_menu!(msg);
+                                            }
+                                        }
+                                    }
+                                } else if let Some(written) = write_tx(
+                                    &mut serial_conn, &bytes, &mut stats, &mut tx_pane, &mut capture, &mut pcap) {
+                                    draw_tx_pane(&tx_pane, &status_bar, has_command_row(&args, &line_editor));
+                                    if echo.enabled {
+                                        print_rx!(echo.render(&bytes[..written]));
+                                    }
+                                }
+                            }
+                            KeyboardInputAction::Paste(bytes) => {
+                                if menu_state.is_open {
+                                    menu_state = menu::handle_chars(menu_state, event.unwrap().unwrap());
+                                    event_type = EventType::Menu;
+                                } else if let (true, Some(Ok(CrosstermEvent::Paste(text)))) = (line_editor.enabled, &event) {
+                                    // pasted lines are sent like typed ones, the rest is left to edit
+                                    paste.push(&line_editor.paste(text, tx_eol, tx_encoding));
+                                    draw_edit_line(&line_editor, &status_bar);
+                                } else {
+                                    paste.push(&bytes);
+                                }
+                            }
+                            KeyboardInputAction::KeypressError => {
+                                log_to_ui!("Keypress error");
+                                log_event(Event::KeypressError);
+                                break 'connection
+                            }
+                            KeyboardInputAction::Menu => {
+                                if args.tui {
+                                    menu_state = menu::open_at_row(menu_state, command_row(&status_bar));
+                                    event_type = EventType::Menu;
+                                } else {
+                                    menu_state = menu::newline(menu_state);
+                                    serial_rx_cursor_position = (
+                                        serial_rx_cursor_position.0,
+                                        menu_state.cursor_position.1 - 1);
+                                    event_type = EventType::Menu;
+                                }
+                            },
+                            KeyboardInputAction::NoAction => {},
+                        };
+                    }
                 },
                 event = serial_rx_event => {
                     match event {
                         Ok(_) => {
                             event_type = EventType::Initial;
 
-                            if ansi_sequence.len() > 0 { // continue buffering an ANSI sequence
-                                assert_ne!(rx_buf[0], 0x1b); // second escape received
-                                ansi_sequence.push(rx_buf[0]);
-                                if rx_buf[0] == 'm' as u8 { // ANSI color finished
-                                    let ansi_string: String = ansi_sequence
-                                        .iter()
-                                        .map(|&c| c as char)
-                                        .collect();
-
-                                    let (col, row) = serial_rx_cursor_position;
-                                    queue!(
-                                        stdout,
-                                        cursor::MoveTo(col, row),
-                                        Print(ansi_string),
-                                    ).unwrap();
-                                    event_type = EventType::SerialRX;
-                                    ansi_sequence.clear();
+                            if let Err(error) = session_log.write(&rx_buf) {
+                                log_to_ui!("Log error '{}', logging stopped", error);
+                                session_log.stop();
+                            }
+                            record(&mut capture, "Capture", Direction::Rx, &rx_buf);
+                            record(&mut pcap, "pcap", Direction::Rx, &rx_buf);
+
+                            let outputs = rx_display.render(rx_buf[0]);
+                            for output in &outputs {
+                                history.push(output);
+                            }
+                            // the pager shows every line, the filter only applies to the display
+                            let newlines = outputs.iter().filter(|output| matches!(output, RxOutput::Newline)).count();
+                            let has_newline = newlines > 0;
+                            let cursor_requested = outputs.iter().any(|output| matches!(output, RxOutput::CursorPositionReport));
+                            stats.rx(1, newlines as u64);
+                            let outputs = line_filter.apply(outputs);
+
+                            if pager_state.is_open || popup.is_some() {
+                                deferred_rx_output.push(outputs);
+                                if has_newline && pager_state.is_open {
+                                    pager_state = pager::update(pager_state, &history);
                                 }
-                            } else if rx_buf[0] == 0x1b { // begin buffering an ANSI sequence
-                                ansi_sequence.push(0x1b);
-                            } else if menu_state.is_open && rx_buf[0] == '\n' as u8 {
-                                // move to the menu line and clear it
-                                queue!(
-                                    stdout,
-                                    cursor::MoveTo(0, menu_state.cursor_position.1),
-                                    Clear(CurrentLine),
-                                ).unwrap();
-
-                                // add the menu back
-                                menu_state = menu::newline(menu_state);
-
-                                // manually set the serial rx cursor up 1 row from the menu
-                                serial_rx_cursor_position = (0, menu_state.cursor_position.1 - 1);
                             } else {
+                                print_rx!(outputs);
+                            }
+                            if cursor_requested {
+                                // answered for the device's part of the screen, rows and columns from 1
+                                if matches!(event_type, EventType::SerialRX) {
+                                    stdout.flush().unwrap();
+                                    serial_rx_cursor_position = cursor::position().unwrap();
+                                }
                                 let (col, row) = serial_rx_cursor_position;
-                                queue!(
-                                    stdout,
-                                    cursor::MoveTo(col, row),
-                                    Print(rx_buf[0] as char),
-                                ).unwrap();
-                                event_type = EventType::SerialRX;
+                                let report = format!("\x1b[{};{}R", row + 1, col + 1);
+                                write_tx(&mut serial_conn, report.as_bytes(), &mut stats, &mut tx_pane, &mut capture, &mut pcap);
                             }
                         }
                         Err(error) => {
                             match error.kind() {
                                 PermissionDenied | TimedOut => {
+                                    log_event(Event::Disconnected(error.kind()));
+                                    if let Err(error) = session_log.mark(
+                                        &format!("{} error '{}', disconnected", args.port(), error.kind())
+                                    ) {
+                                        log_to_ui!("Log error '{}', logging stopped", error);
+                                        session_log.stop();
+                                    }
+                                    if args.tui {
+                                        // the menu starts closed again once reconnected
+                                        if pager_state.is_open {
+                                            pager::close(pager_state);
+                                        }
+                                        popup = None;
+                                        showing_stats = false;
+                                        deferred_rx_output.clear();
+                                        redraw_tui(&history, &line_filter, &highlight_rules, &status_bar, &tx_pane,
+                                            menu::close(menu_state), &popup, &line_editor);
+                                    } else if pager_state.is_open || popup.is_some() {
+                                        if pager_state.is_open {
+                                            pager::close(pager_state);
+                                        }
+                                        if popup.take().is_some() {
+                                            tui::leave();
+                                        }
+                                        showing_stats = false;
+                                        print_rx_output(
+                                            deferred_rx_output.take(), menu_state, serial_rx_cursor_position, saved_rx_cursor_position, args.tui,
+                                            output_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor)));
+                                    }
+                                    if let Some(status_bar) = &mut status_bar {
+                                        status_bar.disconnected();
+                                    }
+                                    draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
+                                    paste.clear();
                                     connect_event_fut.set(wait_for_serial_port(&args, Some(error.kind())).fuse());
                                     break 'communication
                                 },
-                                _ => log_to_ui!("Serial RX Error: {:?}", error)
+                                _ => {
+                                    log_to_ui!("Serial RX Error: {:?}", error);
+                                    log_event(Event::RxError(&error));
+                                    stats.rx_error();
+                                }
+                            }
+                        }
+                    }
+                },
+                _ = rx_flush => {
+                    // the part of a line held back for highlighting, e.g. a prompt
+                    event_type = EventType::Initial;
+                    let outputs = rx_display.flush();
+                    for output in &outputs {
+                        history.push(output);
+                    }
+                    let outputs = line_filter.apply(outputs);
+                    if pager_state.is_open || popup.is_some() {
+                        deferred_rx_output.push(outputs);
+                    } else {
+                        print_rx!(outputs);
+                    }
+                },
+                _ = filter_release => {
+                    // a line the filter held back that may never end, e.g. a prompt
+                    event_type = EventType::Initial;
+                    let outputs = line_filter.release();
+                    if pager_state.is_open || popup.is_some() {
+                        deferred_rx_output.push(outputs);
+                    } else {
+                        print_rx!(outputs);
+                    }
+                },
+                _ = log_flush => {
+                    if let Err(error) = session_log.flush() {
+                        log_to_ui!("Log error '{}', logging stopped", error);
+                        session_log.stop();
+                    }
+                },
+                _ = paste_chunk => {
+                    let chunk = paste.next_chunk();
+                    match write_tx(&mut serial_conn, &chunk, &mut stats, &mut tx_pane, &mut capture, &mut pcap) {
+                        Some(written) => {
+                            paste.unsent(&chunk, written);
+                            draw_tx_pane(&tx_pane, &status_bar, has_command_row(&args, &line_editor));
+                            if echo.enabled && popup.is_none() && !pager_state.is_open {
+                                print_rx!(echo.render(&chunk[..written]));
                             }
                         }
+                        None => paste.clear(),
+                    }
+                },
+                _ = status_tick => {
+                    event_type = EventType::Initial;
+                    if let (true, Some(_)) = (showing_stats, &popup) {
+                        let view = Popup::new("Statistics", stats.lines());
+                        tui::draw_popup(&view, output_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor)));
+                        popup = Some(view);
+                    }
+                    if !pager_state.is_open {
+                        draw_status_bar(&status_bar, stats.rx_bytes, stats.tx_bytes, &session_log);
                     }
                 },
             };
@@ -284,6 +1412,10 @@ async fn io_tasks(args: Args) {
             // update the text and cursor positions if they've changed
             stdout.flush().unwrap();
 
+            if pager_state.is_open || popup.is_some() {
+                continue; // the pager owns the alternate screen and its cursor, a popup hides it
+            }
+
             match &event_type {
                 EventType::Menu => menu_state.cursor_position = cursor::position().unwrap(),
                 EventType::SerialRX => serial_rx_cursor_position = cursor::position().unwrap(),
@@ -297,6 +1429,12 @@ async fn io_tasks(args: Args) {
                     cursor::MoveTo(menu_state.cursor_position.0, menu_state.cursor_position.1),
                 )
                 .unwrap();
+            } else if line_editor.enabled {
+                execute!(
+                    stdout,
+                    cursor::MoveTo(line_editor.cursor_column(), command_row(&status_bar)),
+                )
+                .unwrap();
             } else {
                 execute!(
                     stdout,
@@ -307,4 +1445,16 @@ async fn io_tasks(args: Args) {
             execute!(stdout, cursor::Show).unwrap();
         }
     }
+
+    if reserved_rows(&status_bar, &tx_pane, has_command_row(&args, &line_editor)) > 0 {
+        scroll_region::release(reserved_rows(
+            &status_bar,
+            &tx_pane,
+            has_command_row(&args, &line_editor),
+        ));
+    }
+    if args.tui {
+        tui::leave();
+    }
+    stdout.flush().unwrap();
 }
@@ -1,8 +1,6 @@
-use std::io::stdout;
-
 use crossterm::{
     cursor,
-    event::{Event as CrosstermEvent, KeyCode},
+    event::{Event as CrosstermEvent, KeyCode, KeyModifiers},
     queue,
     style::{
         Color::{DarkGrey, Reset, White},
@@ -12,6 +10,8 @@ use crossterm::{
 };
 use phf::phf_map;
 
+use crate::cast::stdout;
+
 static MENU_COMMANDS: phf::Map<&'static str, Action> = phf_map! {
     "quit" => Action::Quit,
     "q" => Action::Quit,
@@ -20,13 +20,65 @@ static MENU_COMMANDS: phf::Map<&'static str, Action> = phf_map! {
     "help" => Action::Help,
     "h" => Action::Help,
     "?" => Action::Help,
+    "log" => Action::Log,
+    "view" => Action::View,
+    "pcap" => Action::Pcap,
+    "pager" => Action::Pager,
+    "eol" => Action::Eol,
+    "filter" => Action::Filter,
+    "exclude" => Action::Exclude,
+    "control" => Action::ControlChars,
+    "ports" => Action::Ports,
+    "stats" => Action::Stats,
+    "echo" => Action::Echo,
+    "line" => Action::Line,
 };
 
+/// Shown by `help`, one line per command.
+pub const COMMAND_HELP: &[(&str, &str)] = &[
+    ("quit, q", "Exit"),
+    (
+        "timestamp, ts [on|off]",
+        "Log a timestamp, or prefix lines with one",
+    ),
+    ("log start <path> | stop", "Append received data to a file"),
+    ("pcap start <path> | stop", "Write data to a pcapng file"),
+    ("view hex|text", "Show received data as a hex dump or text"),
+    ("eol rx|tx <ending>", "Change the line endings"),
+    (
+        "control raw|caret|pictures|hex",
+        "How control characters are shown",
+    ),
+    ("filter <regex> | off", "Only show matching lines"),
+    ("exclude <regex>", "Hide matching lines"),
+    ("pager", "Scroll through received lines"),
+    ("/<regex>", "Search received lines"),
+    ("ports", "List serial ports"),
+    ("stats", "Show throughput and error counts"),
+    ("echo on|off", "Show what is typed"),
+    ("line on|off", "Edit lines before sending them"),
+    ("help, h, ?", "Show this help"),
+];
+
 #[derive(Clone, Copy, Debug)]
 pub enum Action {
     Quit,
     Timestamp,
     Help,
+    Log,
+    View,
+    Pcap,
+    Pager,
+    Eol,
+    Filter,
+    Exclude,
+    ControlChars,
+    Ports,
+    Stats,
+    Echo,
+    Line,
+    /// `/pattern`, the pattern is the argument
+    Search,
 }
 
 pub struct MenuState {
@@ -34,6 +86,8 @@ pub struct MenuState {
     pub command: String,
     pub cursor_position: (u16, u16),
     pub action: Option<Action>,
+    /// Everything typed after the command name, e.g. "start session.log" for "log start session.log"
+    pub argument: String,
     error: Option<String>,
 }
 
@@ -44,37 +98,66 @@ impl MenuState {
             command: String::from(""),
             cursor_position: position,
             action: None,
+            argument: String::from(""),
             error: None,
         }
     }
 }
 
 pub fn newline(menu_state: MenuState) -> MenuState {
+    queue!(stdout(), Print("\n")).unwrap();
+
+    let menu_state = MenuState {
+        is_open: true,
+        command: menu_state.command,
+        cursor_position: menu_state.cursor_position,
+        action: None,
+        argument: String::from(""),
+        error: None,
+    };
+    draw(&menu_state);
+    menu_state
+}
+
+/// Open the menu on a fixed row, for a layout that keeps a row for it.
+pub fn open_at_row(menu_state: MenuState, row: u16) -> MenuState {
+    let col = 2 + menu_state.command.chars().count() as u16;
+    let menu_state = MenuState {
+        is_open: true,
+        command: menu_state.command,
+        cursor_position: (col, row),
+        action: None,
+        argument: String::from(""),
+        error: None,
+    };
+    draw(&menu_state);
+    menu_state
+}
+
+/// Draw the menu line with the command being typed, or the error from the last one.
+pub fn draw(menu_state: &MenuState) {
+    let text_displayed = match &menu_state.error {
+        None => &menu_state.command,
+        Some(error) => error,
+    };
+
     let (col, _) = size().unwrap();
     let blank_row: String = vec![" "; col.into()].into_iter().collect();
 
     queue!(
         stdout(),
-        Print("\n"),
         cursor::MoveTo(0, menu_state.cursor_position.1),
+        Clear(CurrentLine),
         SetBackgroundColor(DarkGrey),
         SetForegroundColor(White),
         Print(blank_row),
         cursor::MoveTo(0, menu_state.cursor_position.1),
         Print(": "),
-        Print(&menu_state.command),
+        Print(text_displayed),
         SetBackgroundColor(Reset),
         SetForegroundColor(Reset),
     )
     .unwrap();
-
-    return MenuState {
-        is_open: true,
-        command: menu_state.command,
-        cursor_position: menu_state.cursor_position,
-        action: None,
-        error: None,
-    };
 }
 
 pub fn close(menu_state: MenuState) -> MenuState {
@@ -90,6 +173,7 @@ pub fn close(menu_state: MenuState) -> MenuState {
         command: menu_state.command,
         cursor_position: menu_state.cursor_position,
         action: None,
+        argument: String::from(""),
         error: None,
     };
 }
@@ -100,12 +184,15 @@ pub fn handle_chars(menu_state: MenuState, event: CrosstermEvent) -> MenuState {
         command: menu_state.command,
         cursor_position: menu_state.cursor_position,
         action: None,
+        argument: String::from(""),
         error: None,
     };
 
     match event {
         CrosstermEvent::Key(key) => {
             match key.code {
+                // control keys have no meaning in a command, e.g. Ctrl-D is not a `d`
+                KeyCode::Char(_) if key.modifiers.contains(KeyModifiers::CONTROL) => {}
                 KeyCode::Char(code) => {
                     new_menu_state.command.push(code);
                     new_menu_state.cursor_position = (
@@ -118,16 +205,20 @@ pub fn handle_chars(menu_state: MenuState, event: CrosstermEvent) -> MenuState {
                         // clear the previous error and reveal the bad command but don't try to
                         // execute it again
                         new_menu_state.error = None;
-                    } else if MENU_COMMANDS.contains_key(&new_menu_state.command) {
-                        new_menu_state.action = Some(MENU_COMMANDS[&new_menu_state.command]);
+                    } else if let Some(pattern) = new_menu_state.command.strip_prefix('/') {
+                        new_menu_state.action = Some(Action::Search);
+                        new_menu_state.argument = pattern.to_string();
+                        new_menu_state.command.clear();
+                    } else if let Some(action) = MENU_COMMANDS.get(command_name(&new_menu_state)) {
+                        new_menu_state.action = Some(*action);
+                        new_menu_state.argument = command_argument(&new_menu_state).to_string();
+                        new_menu_state.command.clear();
                     } else {
                         new_menu_state.error =
                             Some(format!("{} is an unknown command", new_menu_state.command));
                     }
                 }
-                KeyCode::Esc => {
-                    // TODO: back key
-                }
+                KeyCode::Esc => return close(new_menu_state),
                 KeyCode::Backspace => {
                     if new_menu_state.command.len() > 0 {
                         new_menu_state.command.pop();
@@ -136,35 +227,36 @@ pub fn handle_chars(menu_state: MenuState, event: CrosstermEvent) -> MenuState {
                 _ => {}
             }
         }
+        CrosstermEvent::Paste(text) => {
+            // a command is one line, pasted control characters and later lines are dropped
+            let line = text.lines().next().unwrap_or_default();
+            let pasted: String = line.chars().filter(|c| !c.is_control()).collect();
+            new_menu_state.cursor_position = (
+                new_menu_state.cursor_position.0 + pasted.chars().count() as u16,
+                new_menu_state.cursor_position.1,
+            );
+            new_menu_state.command.push_str(&pasted);
+        }
         CrosstermEvent::FocusGained
         | CrosstermEvent::FocusLost
         | CrosstermEvent::Mouse(_)
-        | CrosstermEvent::Resize(_, _)
-        | CrosstermEvent::Paste(_) => {}
-    };
-
-    let text_displayed = match &new_menu_state.error {
-        None => &new_menu_state.command,
-        Some(error) => &error,
+        | CrosstermEvent::Resize(_, _) => {}
     };
 
-    let (col, _) = size().unwrap();
-    let blank_row: String = vec![" "; col.into()].into_iter().collect();
+    draw(&new_menu_state);
+    new_menu_state
+}
 
-    queue!(
-        stdout(),
-        cursor::MoveTo(0, new_menu_state.cursor_position.1),
-        Clear(CurrentLine),
-        SetBackgroundColor(DarkGrey),
-        SetForegroundColor(White),
-        Print(blank_row),
-        cursor::MoveTo(0, new_menu_state.cursor_position.1),
-        Print(": "),
-        Print(text_displayed),
-        SetBackgroundColor(Reset),
-        SetForegroundColor(Reset),
-    )
-    .unwrap();
+fn command_name(menu_state: &MenuState) -> &str {
+    match menu_state.command.trim().split_once(' ') {
+        Some((name, _)) => name,
+        None => menu_state.command.trim(),
+    }
+}
 
-    new_menu_state
+fn command_argument(menu_state: &MenuState) -> &str {
+    match menu_state.command.trim().split_once(' ') {
+        Some((_, argument)) => argument.trim(),
+        None => "",
+    }
 }
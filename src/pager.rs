@@ -0,0 +1,298 @@
+use crossterm::{
+    cursor,
+    event::{Event as CrosstermEvent, KeyCode},
+    queue,
+    style::{
+        Color::{Black, DarkGrey, DarkYellow, Reset, White, Yellow},
+        Print, SetBackgroundColor, SetForegroundColor,
+    },
+    terminal::{size, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use regex::Regex;
+
+use crate::cast::stdout;
+use crate::history::History;
+
+/// Scrolls through the history on the alternate screen, leaving the session's screen intact.
+pub struct PagerState {
+    pub is_open: bool,
+    /// Index of the history line at the top of the screen, which stays on the same line as older
+    /// lines are dropped
+    top: usize,
+    /// Keep the newest lines in view as they arrive
+    following: bool,
+    search: Option<Regex>,
+    /// Index of the history line with the match that was jumped to
+    current_match: Option<usize>,
+}
+
+impl PagerState {
+    pub fn new() -> PagerState {
+        PagerState {
+            is_open: false,
+            top: 0,
+            following: true,
+            search: None,
+            current_match: None,
+        }
+    }
+}
+
+fn page_rows() -> usize {
+    let (_, rows) = size().unwrap();
+    // the bottom row is the status bar
+    usize::from(rows.max(2) - 1)
+}
+
+fn last_top(history: &History) -> usize {
+    (history.last() + 1)
+        .saturating_sub(page_rows())
+        .max(history.first())
+}
+
+pub fn open(history: &History) -> PagerState {
+    let pager_state = PagerState {
+        is_open: true,
+        top: last_top(history),
+        following: true,
+        search: None,
+        current_match: None,
+    };
+    queue!(stdout(), EnterAlternateScreen).unwrap();
+    draw(&pager_state, history);
+    pager_state
+}
+
+/// The next line after `from` with a match, or before it if not `forward`, wrapping around.
+fn find(history: &History, search: &Regex, from: Option<usize>, forward: bool) -> Option<usize> {
+    let first = history.first();
+    let count = history.line_count();
+    // offsets from the oldest line, a match that has since been dropped starts over
+    let from = from.and_then(|from| from.checked_sub(first));
+    (1..=count)
+        .map(|step| match (from, forward) {
+            (None, true) => step - 1,
+            (None, false) => count - step,
+            (Some(from), true) => (from + step) % count,
+            (Some(from), false) => (from + count - step % count) % count,
+        })
+        .map(|offset| first + offset)
+        .find(|&index| search.is_match(history.line(index)))
+}
+
+/// Scroll so that the matching line is a third of the way down the page.
+fn jump_to(pager_state: PagerState, history: &History, line: usize) -> PagerState {
+    let last = last_top(history);
+    let top = line
+        .saturating_sub(page_rows() / 3)
+        .max(history.first())
+        .min(last);
+    PagerState {
+        top,
+        following: top == last,
+        current_match: Some(line),
+        ..pager_state
+    }
+}
+
+/// Open the pager at the first line matching `search`, or return None if there is no match.
+pub fn search(history: &History, search: Regex) -> Option<PagerState> {
+    let line = find(history, &search, None, true)?;
+    let pager_state = jump_to(open(history), history, line);
+    let pager_state = PagerState {
+        search: Some(search),
+        ..pager_state
+    };
+    draw(&pager_state, history);
+    Some(pager_state)
+}
+
+pub fn close(pager_state: PagerState) -> PagerState {
+    queue!(stdout(), LeaveAlternateScreen).unwrap();
+    PagerState {
+        is_open: false,
+        ..pager_state
+    }
+}
+
+/// Redraw with newly received lines, scrolling if the end is in view. Otherwise the view stays
+/// on the same lines until they are dropped.
+pub fn update(pager_state: PagerState, history: &History) -> PagerState {
+    let top = match pager_state.following {
+        true => last_top(history),
+        false => pager_state.top.max(history.first()),
+    };
+    let pager_state = PagerState { top, ..pager_state };
+    draw(&pager_state, history);
+    pager_state
+}
+
+pub fn handle_event(
+    pager_state: PagerState,
+    history: &History,
+    event: CrosstermEvent,
+) -> PagerState {
+    let page = page_rows();
+    let first = history.first();
+    let last = last_top(history);
+    let top = pager_state.top.max(first);
+
+    let top = match event {
+        CrosstermEvent::Key(key) => match (key.code, &pager_state.search) {
+            (KeyCode::Char('q'), _) | (KeyCode::Esc, _) => return close(pager_state),
+            (KeyCode::Char('n'), Some(search)) | (KeyCode::Char('N'), Some(search)) => {
+                let forward = key.code == KeyCode::Char('n');
+                let from = pager_state.current_match;
+                let pager_state = match find(history, search, from, forward) {
+                    Some(line) => jump_to(pager_state, history, line),
+                    None => pager_state,
+                };
+                draw(&pager_state, history);
+                return pager_state;
+            }
+            (KeyCode::PageUp, _) => top.saturating_sub(page).max(first),
+            (KeyCode::PageDown, _) | (KeyCode::Char(' '), _) => (top + page).min(last),
+            (KeyCode::Up, _) => top.saturating_sub(1).max(first),
+            (KeyCode::Down, _) | (KeyCode::Enter, _) => (top + 1).min(last),
+            (KeyCode::Home, _) => first,
+            (KeyCode::End, _) => last,
+            _ => top,
+        },
+        CrosstermEvent::Resize(_, _) => top.min(last),
+        CrosstermEvent::FocusGained
+        | CrosstermEvent::FocusLost
+        | CrosstermEvent::Mouse(_)
+        | CrosstermEvent::Paste(_) => top,
+    };
+
+    let pager_state = PagerState {
+        top,
+        following: top == last,
+        ..pager_state
+    };
+    draw(&pager_state, history);
+    pager_state
+}
+
+fn draw(pager_state: &PagerState, history: &History) {
+    let (cols, rows) = size().unwrap();
+    let page = page_rows();
+    let mut stdout = stdout();
+
+    queue!(
+        stdout,
+        cursor::Hide,
+        cursor::MoveTo(0, 0),
+        Clear(ClearType::All)
+    )
+    .unwrap();
+    for row in 0..page {
+        let index = pager_state.top + row;
+        if index > history.last() {
+            break;
+        }
+        let line: String = history.line(index).chars().take(cols.into()).collect();
+        queue!(stdout, cursor::MoveTo(0, row as u16)).unwrap();
+        match &pager_state.search {
+            Some(search) => {
+                let highlight = match pager_state.current_match == Some(index) {
+                    true => Yellow,
+                    false => DarkYellow,
+                };
+                let mut printed = 0;
+                for found in search.find_iter(&line) {
+                    queue!(
+                        stdout,
+                        Print(&line[printed..found.start()]),
+                        SetBackgroundColor(highlight),
+                        SetForegroundColor(Black),
+                        Print(found.as_str()),
+                        SetBackgroundColor(Reset),
+                        SetForegroundColor(Reset),
+                    )
+                    .unwrap();
+                    printed = found.end();
+                }
+                queue!(stdout, Print(&line[printed..])).unwrap();
+            }
+            None => queue!(stdout, Print(line)).unwrap(),
+        };
+    }
+
+    // numbered from the oldest line kept
+    let first_shown = pager_state.top.saturating_sub(history.first()) + 1;
+    let last_shown = (first_shown - 1 + page).min(history.line_count());
+    let status = match &pager_state.search {
+        Some(search) => format!(
+            " /{}  lines {}-{} of {}  n/N for next/previous match, q to close",
+            search,
+            first_shown,
+            last_shown,
+            history.line_count()
+        ),
+        None => format!(
+            " lines {}-{} of {}  PgUp/PgDn/Home/End to scroll, q to close",
+            first_shown,
+            last_shown,
+            history.line_count()
+        ),
+    };
+    let blank_row: String = vec![" "; cols.into()].into_iter().collect();
+    queue!(
+        stdout,
+        cursor::MoveTo(0, rows - 1),
+        SetBackgroundColor(DarkGrey),
+        SetForegroundColor(White),
+        Print(blank_row),
+        cursor::MoveTo(0, rows - 1),
+        Print(status.chars().take(cols.into()).collect::<String>()),
+        SetBackgroundColor(Reset),
+        SetForegroundColor(Reset),
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rx_display::RxOutput;
+
+    fn history(max_lines: usize, text: &str) -> History {
+        let mut history = History::new(max_lines);
+        for line in text.split_inclusive('\n') {
+            history.push(&RxOutput::Print(String::from(line.trim_end_matches('\n'))));
+            if line.ends_with('\n') {
+                history.push(&RxOutput::Newline);
+            }
+        }
+        history
+    }
+
+    #[test]
+    fn finds_matches_wrapping_around() {
+        let history = history(10, "a1\nb\na2\nc");
+        let search = Regex::new("a").unwrap();
+        assert_eq!(find(&history, &search, None, true), Some(0));
+        assert_eq!(find(&history, &search, None, false), Some(2));
+        assert_eq!(find(&history, &search, Some(0), true), Some(2));
+        assert_eq!(find(&history, &search, Some(2), true), Some(0));
+        assert_eq!(find(&history, &search, Some(0), false), Some(2));
+        assert_eq!(find(&history, &search, Some(3), true), Some(0));
+        assert_eq!(
+            find(&history, &Regex::new("x").unwrap(), Some(0), true),
+            None
+        );
+    }
+
+    #[test]
+    fn starts_over_from_a_dropped_match() {
+        let history = history(2, "a1\nb\na2\nd");
+        let search = Regex::new("a").unwrap();
+        assert_eq!(history.first(), 1);
+        assert_eq!(find(&history, &search, Some(0), true), Some(2));
+        // the only match is found again from itself
+        assert_eq!(find(&history, &search, Some(2), true), Some(2));
+        assert_eq!(find(&history, &search, Some(2), false), Some(2));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::encoding::Encoding;
+use crate::line_ending::LineEnding;
+
+/// Shortest wait before offering the port a chunk it took none of, even with no paste delay.
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Pasted bytes waiting to be sent, a chunk at a time so that a long paste does not overrun the
+/// device's receive buffer.
+pub struct Paste {
+    chunk_size: usize,
+    delay: Duration,
+    pending: VecDeque<u8>,
+    /// When the next chunk is due
+    next_chunk_at: Instant,
+}
+
+impl Paste {
+    pub fn new(chunk_size: usize, delay: Duration) -> Paste {
+        Paste {
+            chunk_size,
+            delay,
+            pending: VecDeque::new(),
+            next_chunk_at: Instant::now(),
+        }
+    }
+
+    /// Queue pasted bytes, the first chunk is sent straight away unless a paste is in progress.
+    pub fn push(&mut self, bytes: &[u8]) {
+        if self.pending.is_empty() {
+            self.next_chunk_at = Instant::now();
+        }
+        self.pending.extend(bytes);
+    }
+
+    /// When the next chunk is due, or None if there is nothing to send.
+    pub fn deadline(&self) -> Option<Instant> {
+        (!self.pending.is_empty()).then_some(self.next_chunk_at)
+    }
+
+    /// Take the next chunk, the next one is due after the delay.
+    pub fn next_chunk(&mut self) -> Vec<u8> {
+        let len = self.chunk_size.min(self.pending.len());
+        self.next_chunk_at = Instant::now() + self.delay;
+        self.pending.drain(..len).collect()
+    }
+
+    /// Put back the end of a chunk the port did not take all of, to be sent first.
+    pub fn unsent(&mut self, chunk: &[u8], written: usize) {
+        for &byte in chunk[written..].iter().rev() {
+            self.pending.push_front(byte);
+        }
+        if written == 0 {
+            self.next_chunk_at = self.next_chunk_at.max(Instant::now() + RETRY_DELAY);
+        }
+    }
+
+    /// Drop what is left of a paste, e.g. when the port disconnects.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// The lines of pasted text, split at each CR, LF, or CRLF.
+pub fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split("\r\n").flat_map(|part| part.split(['\r', '\n']))
+}
+
+/// The bytes for pasted text, with each CR, LF, or CRLF sent as the TX line ending.
+pub fn encode(text: &str, tx_eol: LineEnding, tx_encoding: Encoding) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for (index, line) in lines(text).enumerate() {
+        if index > 0 {
+            bytes.extend_from_slice(tx_eol.bytes());
+        }
+        bytes.extend(tx_encoding.encode(line));
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_each_line_break_as_the_tx_line_ending() {
+        let text = "a\r\nb\rc\nd";
+        assert_eq!(encode(text, LineEnding::Cr, Encoding::Utf8), b"a\rb\rc\rd");
+        assert_eq!(encode(text, LineEnding::Lf, Encoding::Utf8), b"a\nb\nc\nd");
+        assert_eq!(
+            encode(text, LineEnding::CrLf, Encoding::Utf8),
+            b"a\r\nb\r\nc\r\nd"
+        );
+    }
+
+    #[test]
+    fn keeps_blank_and_trailing_lines() {
+        assert_eq!(
+            encode("a\n\nb\n", LineEnding::CrLf, Encoding::Utf8),
+            b"a\r\n\r\nb\r\n"
+        );
+        assert_eq!(encode("", LineEnding::CrLf, Encoding::Utf8), b"");
+    }
+
+    #[test]
+    fn encodes_pasted_text() {
+        assert_eq!(encode("é\n", LineEnding::Cr, Encoding::Latin1), b"\xe9\r");
+    }
+
+    #[test]
+    fn sends_in_chunks() {
+        let mut paste = Paste::new(4, Duration::ZERO);
+        assert!(paste.deadline().is_none());
+        paste.push(b"0123456789");
+        assert_eq!(paste.next_chunk(), b"0123");
+        assert_eq!(paste.next_chunk(), b"4567");
+        assert_eq!(paste.next_chunk(), b"89");
+        assert!(paste.deadline().is_none());
+    }
+
+    #[test]
+    fn resends_what_the_port_did_not_take() {
+        let mut paste = Paste::new(4, Duration::ZERO);
+        paste.push(b"012345");
+        let chunk = paste.next_chunk();
+        paste.unsent(&chunk, 1);
+        assert!(paste.deadline().unwrap() <= Instant::now());
+        assert_eq!(paste.next_chunk(), b"1234");
+
+        // nothing taken, the retry waits even without a delay
+        let chunk = paste.next_chunk();
+        paste.unsent(&chunk, 0);
+        assert!(paste.deadline().unwrap() > Instant::now());
+        assert_eq!(paste.next_chunk(), b"5");
+    }
+}
@@ -0,0 +1,220 @@
+//! pcapng export of serial traffic, see https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-01.html
+//!
+//! Each chunk of bytes is one packet on a single LINKTYPE_USER0 interface, so a custom
+//! dissector can be assigned to DLT 147 in Wireshark. The direction is in the epb_flags option.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::capture::{coalesce, Direction, PendingChunk, Recorder};
+use crate::rotation::{RotatingFile, Rotation};
+
+const LINKTYPE_USER0: u16 = 147;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+const OPT_ENDOFOPT: u16 = 0;
+const IF_NAME: u16 = 2;
+const EPB_FLAGS: u16 = 2;
+
+const EPB_FLAGS_INBOUND: u32 = 0b01;
+const EPB_FLAGS_OUTBOUND: u32 = 0b10;
+
+pub struct Pcap {
+    writer: RotatingFile,
+    pub path: PathBuf,
+    /// Wall clock time at `start`, pcapng timestamps are absolute
+    start_time: SystemTime,
+    start: Instant,
+    pending: Option<PendingChunk>,
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// A block of the given type with `body` padded to 32 bits and the length at both ends.
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let total_len = (12 + padded(body.len())) as u32;
+    let mut block = Vec::with_capacity(total_len as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&total_len.to_le_bytes());
+    block.extend_from_slice(body);
+    block.resize(8 + padded(body.len()), 0);
+    block.extend_from_slice(&total_len.to_le_bytes());
+    block
+}
+
+fn option(code: u16, value: &[u8]) -> Vec<u8> {
+    let mut option = Vec::with_capacity(4 + padded(value.len()));
+    option.extend_from_slice(&code.to_le_bytes());
+    option.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    option.extend_from_slice(value);
+    option.resize(4 + padded(value.len()), 0);
+    option
+}
+
+impl Pcap {
+    pub fn create(path: &Path, port: &str, rotation: Rotation) -> io::Result<Pcap> {
+        // every rotated file starts with its own section and interface
+        let mut header = Vec::new();
+
+        let mut section_header = Vec::new();
+        section_header.extend_from_slice(&0x1A2B_3C4Du32.to_le_bytes()); // byte-order magic
+        section_header.extend_from_slice(&1u16.to_le_bytes()); // major version
+        section_header.extend_from_slice(&0u16.to_le_bytes()); // minor version
+        section_header.extend_from_slice(&(-1i64).to_le_bytes()); // section length unknown
+        header.extend(block(SECTION_HEADER_BLOCK, &section_header));
+
+        let mut interface_description = Vec::new();
+        interface_description.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+        interface_description.extend_from_slice(&0u16.to_le_bytes()); // reserved
+        interface_description.extend_from_slice(&0u32.to_le_bytes()); // no snap length limit
+        interface_description.extend(option(IF_NAME, port.as_bytes()));
+        interface_description.extend(option(OPT_ENDOFOPT, &[]));
+        header.extend(block(INTERFACE_DESCRIPTION_BLOCK, &interface_description));
+
+        Ok(Pcap {
+            writer: RotatingFile::create(path, rotation, &header)?,
+            path: path.to_path_buf(),
+            start_time: SystemTime::now(),
+            start: Instant::now(),
+            pending: None,
+        })
+    }
+
+    fn write_packet(&mut self, chunk: PendingChunk) -> io::Result<()> {
+        let time = self.start_time + (chunk.time - self.start);
+        let micros = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let flags = match chunk.direction {
+            Direction::Rx => EPB_FLAGS_INBOUND,
+            Direction::Tx => EPB_FLAGS_OUTBOUND,
+        };
+
+        let mut enhanced_packet = Vec::with_capacity(32 + padded(chunk.data.len()));
+        enhanced_packet.extend_from_slice(&0u32.to_le_bytes()); // interface id
+        enhanced_packet.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        enhanced_packet.extend_from_slice(&(micros as u32).to_le_bytes());
+        enhanced_packet.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes()); // captured
+        enhanced_packet.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes()); // original
+        enhanced_packet.extend_from_slice(&chunk.data);
+        enhanced_packet.resize(20 + padded(chunk.data.len()), 0);
+        enhanced_packet.extend(option(EPB_FLAGS, &flags.to_le_bytes()));
+        enhanced_packet.extend(option(OPT_ENDOFOPT, &[]));
+
+        // one write per block so that rotation never splits a packet
+        self.writer
+            .write_all(&block(ENHANCED_PACKET_BLOCK, &enhanced_packet))
+    }
+
+    /// Write out the packet that is still collecting bytes.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.pending.take() {
+            Some(pending) => self.write_packet(pending),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Recorder for Pcap {
+    fn record(&mut self, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        match coalesce(&mut self.pending, direction, bytes) {
+            Some(complete) => self.write_packet(complete),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Pcap {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Handle the menu command `pcap start <path>` or `pcap stop`, returning a message for the UI.
+pub fn pcap_command(
+    pcap: &mut Option<Pcap>,
+    argument: &str,
+    port: &str,
+    rotation: Rotation,
+) -> String {
+    match argument.split_once(' ') {
+        Some(("start", path)) => match Pcap::create(Path::new(path.trim()), port, rotation) {
+            Ok(new_pcap) => {
+                *pcap = Some(new_pcap);
+                format!("Writing pcap to {}", path.trim())
+            }
+            Err(error) => format!("Unable to write pcap to {}: {}", path.trim(), error),
+        },
+        None if argument == "stop" => match pcap.take() {
+            Some(stopped_pcap) => {
+                format!("Stopped writing pcap to {}", stopped_pcap.path.display())
+            }
+            None => String::from("Not writing pcap"),
+        },
+        _ => String::from("Usage: pcap start <path> | pcap stop"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_blocks_and_options_to_32_bits() {
+        assert_eq!(
+            block(ENHANCED_PACKET_BLOCK, b"abcde"),
+            b"\x06\0\0\0\x14\0\0\0abcde\0\0\0\x14\0\0\0"
+        );
+        assert_eq!(option(IF_NAME, b"COM3x"), b"\x02\0\x05\0COM3x\0\0\0");
+        assert_eq!(option(OPT_ENDOFOPT, &[]), [0; 4]);
+    }
+
+    #[test]
+    fn writes_a_packet_per_chunk() {
+        let path = std::env::temp_dir().join(format!("pcap-{}.pcapng", std::process::id()));
+        let mut pcap = Pcap::create(&path, "COM3", Rotation::default()).unwrap();
+        pcap.record(Direction::Tx, b"abcde").unwrap();
+        drop(pcap);
+
+        let file = std::fs::read(&path).unwrap();
+        let u32_at = |at: usize| u32::from_le_bytes(file[at..at + 4].try_into().unwrap());
+        assert_eq!(file.len(), 28 + 32 + 52);
+
+        // section header, then the interface with its name
+        assert_eq!(
+            (u32_at(0), u32_at(4), u32_at(24)),
+            (SECTION_HEADER_BLOCK, 28, 28)
+        );
+        assert_eq!(u32_at(8), 0x1A2B_3C4D);
+        assert_eq!(
+            (u32_at(28), u32_at(32), u32_at(56)),
+            (INTERFACE_DESCRIPTION_BLOCK, 32, 32)
+        );
+        assert_eq!(&file[36..38], LINKTYPE_USER0.to_le_bytes());
+        assert_eq!(&file[44..52], option(IF_NAME, b"COM3"));
+
+        // the packet, padded, with its direction in the flags
+        assert_eq!(
+            (u32_at(60), u32_at(64), u32_at(108)),
+            (ENHANCED_PACKET_BLOCK, 52, 52)
+        );
+        assert_eq!(u32_at(68), 0); // interface id
+        assert_eq!((u32_at(80), u32_at(84)), (5, 5));
+        assert_eq!(&file[88..96], b"abcde\0\0\0");
+        assert_eq!(
+            &file[96..104],
+            option(EPB_FLAGS, &EPB_FLAGS_OUTBOUND.to_le_bytes())
+        );
+        assert_eq!(&file[104..108], [0; 4]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDateTime;
+
+use crate::log_to_ui::{log_to_ui, print_log_to_stdout};
+
+const FILE_NAME_TIMESTAMP: &str = "%Y%m%d-%H%M%S";
+
+/// After a failed rotation the current file is kept, and rotation is tried again this much later.
+const ROTATION_RETRY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interval {
+    Hourly,
+    Daily,
+}
+
+/// When to switch to a new file and how many old files to keep; all off by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rotation {
+    pub max_size: Option<u64>,
+    pub interval: Option<Interval>,
+    pub keep: Option<usize>,
+}
+
+impl Rotation {
+    pub fn is_enabled(&self) -> bool {
+        self.max_size.is_some() || self.interval.is_some()
+    }
+}
+
+/// A file that is replaced by a new timestamped file when it grows too large or too old.
+///
+/// With rotation enabled `session.log` is written as `session-20230114-093000.log` and so on,
+/// otherwise it is used as is. `header` starts every new file.
+pub struct RotatingFile {
+    base_path: PathBuf,
+    rotation: Rotation,
+    header: Vec<u8>,
+    append: bool,
+    file: File,
+    size: u64,
+    period: Option<String>,
+    retry_at: Option<Instant>,
+}
+
+fn period(interval: Option<Interval>) -> Option<String> {
+    let now = chrono::offset::Local::now();
+    match interval? {
+        Interval::Hourly => Some(now.format("%Y%m%d%H").to_string()),
+        Interval::Daily => Some(now.format("%Y%m%d").to_string()),
+    }
+}
+
+fn stem_and_extension(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+    let extension = path
+        .extension()
+        .map_or(String::new(), |ext| format!(".{}", ext.to_string_lossy()));
+    (stem, extension)
+}
+
+/// True if `file_name` is `<stem>-<timestamp>[-n]<extension>`, i.e. one of our rotated files.
+fn is_rotated_name(file_name: &str, stem: &str, extension: &str) -> bool {
+    let middle = file_name
+        .strip_prefix(stem)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(extension));
+    match middle {
+        Some(middle) => {
+            let timestamp = middle.get(..15).unwrap_or(middle);
+            NaiveDateTime::parse_from_str(timestamp, FILE_NAME_TIMESTAMP).is_ok()
+        }
+        None => false,
+    }
+}
+
+impl RotatingFile {
+    /// Append to the file, or start a new one if rotation is enabled.
+    pub fn append(path: &Path, rotation: Rotation) -> io::Result<RotatingFile> {
+        RotatingFile::open(path, rotation, &[], true)
+    }
+
+    /// Replace the file, beginning with `header`, or start a new one if rotation is enabled.
+    pub fn create(path: &Path, rotation: Rotation, header: &[u8]) -> io::Result<RotatingFile> {
+        RotatingFile::open(path, rotation, header, false)
+    }
+
+    fn open(
+        path: &Path,
+        rotation: Rotation,
+        header: &[u8],
+        append: bool,
+    ) -> io::Result<RotatingFile> {
+        let mut rotating_file = RotatingFile {
+            base_path: path.to_path_buf(),
+            rotation,
+            header: header.to_vec(),
+            append,
+            file: RotatingFile::open_next(path, rotation, append)?,
+            size: 0,
+            period: period(rotation.interval),
+            retry_at: None,
+        };
+        rotating_file.size = start_file(&mut rotating_file.file, header)?;
+        rotating_file.remove_old_files()?;
+        Ok(rotating_file)
+    }
+
+    fn open_next(path: &Path, rotation: Rotation, append: bool) -> io::Result<File> {
+        let path = match rotation.is_enabled() {
+            true => RotatingFile::timestamped_path(path),
+            false => path.to_path_buf(),
+        };
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+    }
+
+    fn timestamped_path(path: &Path) -> PathBuf {
+        let (stem, extension) = stem_and_extension(path);
+        let timestamp = chrono::offset::Local::now().format(FILE_NAME_TIMESTAMP);
+        let mut timestamped = path.with_file_name(format!("{}-{}{}", stem, timestamp, extension));
+        let mut n = 1;
+        while timestamped.exists() {
+            // more than one rotation within a second
+            timestamped = path.with_file_name(format!("{}-{}-{}{}", stem, timestamp, n, extension));
+            n += 1;
+        }
+        timestamped
+    }
+
+    /// Start files from now on with `header`.
+    pub fn set_header(&mut self, header: &[u8]) {
+        self.header = header.to_vec();
+    }
+
+    /// Whether the next write goes to a new file.
+    pub fn is_due(&self) -> bool {
+        if self
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return false;
+        }
+        let too_large = match self.rotation.max_size {
+            Some(max_size) => self.size >= max_size && self.size > self.header.len() as u64,
+            None => false,
+        };
+        too_large || (self.period.is_some() && period(self.rotation.interval) != self.period)
+    }
+
+    /// Continue in a new file, removing the oldest beyond `keep`. The current file is kept if
+    /// the new one cannot be started.
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut file = RotatingFile::open_next(&self.base_path, self.rotation, self.append)?;
+        let size = start_file(&mut file, &self.header)?;
+        // the old file is flushed as it is dropped
+        self.file = file;
+        self.size = size;
+        self.period = period(self.rotation.interval);
+        self.retry_at = None;
+        if let Err(error) = self.remove_old_files() {
+            log_to_ui!(
+                "Unable to remove old files of {}: {}",
+                self.base_path.display(),
+                error
+            );
+        }
+        Ok(())
+    }
+
+    fn remove_old_files(&self) -> io::Result<()> {
+        let keep = match self.rotation.keep {
+            Some(keep) if self.rotation.is_enabled() => keep.max(1),
+            _ => return Ok(()),
+        };
+        let (stem, extension) = stem_and_extension(&self.base_path);
+        let directory = match self.base_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut rotated_files: Vec<(std::time::SystemTime, PathBuf)> = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if is_rotated_name(&file_name, &stem, &extension) {
+                rotated_files.push((entry.metadata()?.modified()?, entry.path()));
+            }
+        }
+        rotated_files.sort();
+
+        let excess = rotated_files.len().saturating_sub(keep);
+        for (_, path) in rotated_files.into_iter().take(excess) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the header if the file is empty, returning the size of the file.
+fn start_file(file: &mut File, header: &[u8]) -> io::Result<u64> {
+    let size = file.metadata()?.len();
+    if size > 0 {
+        return Ok(size);
+    }
+    file.write_all(header)?;
+    Ok(header.len() as u64)
+}
+
+impl Write for RotatingFile {
+    /// Rotation only happens between writes, so each write lands whole in one file.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_due() {
+            if let Err(error) = self.rotate() {
+                log_to_ui!("Unable to rotate {}: {}", self.base_path.display(), error);
+                self.retry_at = Some(Instant::now() + ROTATION_RETRY);
+            }
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for a test's files.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rotation-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rotated_files(dir: &Path, stem: &str, extension: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| is_rotated_name(&entry.file_name().to_string_lossy(), stem, extension))
+            .map(|entry| entry.path())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn splits_stem_and_extension() {
+        assert_eq!(
+            stem_and_extension(Path::new("logs/session.log")),
+            (String::from("session"), String::from(".log"))
+        );
+        assert_eq!(
+            stem_and_extension(Path::new("capture")),
+            (String::from("capture"), String::new())
+        );
+    }
+
+    #[test]
+    fn recognizes_rotated_names() {
+        assert!(is_rotated_name(
+            "session-20230114-093000.log",
+            "session",
+            ".log"
+        ));
+        assert!(is_rotated_name(
+            "session-20230114-093000-2.log",
+            "session",
+            ".log"
+        ));
+        assert!(is_rotated_name("capture-20230114-093000", "capture", ""));
+        assert!(!is_rotated_name("session.log", "session", ".log"));
+        assert!(!is_rotated_name("session-old.log", "session", ".log"));
+        assert!(!is_rotated_name(
+            "session-20231399-093000.log",
+            "session",
+            ".log"
+        ));
+        assert!(!is_rotated_name(
+            "other-20230114-093000.log",
+            "session",
+            ".log"
+        ));
+        assert!(!is_rotated_name(
+            "session-20230114-093000.txt",
+            "session",
+            ".log"
+        ));
+    }
+
+    #[test]
+    fn uses_the_path_as_is_without_rotation() {
+        let dir = test_dir("plain");
+        let path = dir.join("session.log");
+        let mut file = RotatingFile::create(&path, Rotation::default(), b"header\n").unwrap();
+        file.write_all(b"data\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"header\ndata\n");
+        assert!(rotated_files(&dir, "session", ".log").is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_the_newest() {
+        let dir = test_dir("size");
+        let path = dir.join("session.log");
+        let rotation = Rotation {
+            max_size: Some(10),
+            interval: None,
+            keep: Some(2),
+        };
+        let mut file = RotatingFile::create(&path, rotation, b"h\n").unwrap();
+        for chunk in [&b"first line\n"[..], b"second line\n", b"third line\n"] {
+            file.write_all(chunk).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert!(!path.exists());
+        let files = rotated_files(&dir, "session", ".log");
+        assert_eq!(files.len(), 2);
+        let mut contents: Vec<Vec<u8>> = files.iter().map(|file| fs::read(file).unwrap()).collect();
+        contents.sort();
+        assert_eq!(
+            contents,
+            vec![b"h\nsecond line\n".to_vec(), b"h\nthird line\n".to_vec()]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn does_not_rotate_a_file_with_only_its_header() {
+        let dir = test_dir("header");
+        let path = dir.join("capture.jsonl");
+        let rotation = Rotation {
+            max_size: Some(1),
+            interval: None,
+            keep: None,
+        };
+        let file = RotatingFile::create(&path, rotation, b"header\n").unwrap();
+        assert!(!file.is_due());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
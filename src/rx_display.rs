@@ -0,0 +1,625 @@
+use std::fmt::{Display, Formatter, Result};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::constants::TIMESTAMP_FORMAT;
+use crate::encoding::{Decoder, Encoding};
+use crate::escape_parser::{EscapeParser, Token};
+use crate::highlight::{highlight, HighlightRule};
+use crate::line_ending::LineEnding;
+
+const HEX_ROW_LEN: usize = 16;
+
+/// Longest a line is held back to be highlighted before what has arrived is shown, e.g. a prompt.
+const HELD_LINE_DELAY: Duration = Duration::from_millis(50);
+
+/// What the UI should do with a received byte once it has been rendered.
+pub enum RxOutput {
+    Print(String),
+    Newline,
+    /// Clear the rows of received data, for the device clearing the screen
+    ClearScreen,
+    /// Clear from the cursor to the end of the rows of received data
+    ClearBelow,
+    /// Move within the rows of received data, counted from 0, for the device moving its cursor
+    MoveTo(u16, u16),
+    /// Remember the device's cursor, apart from the one the UI saves for itself
+    SaveCursor,
+    /// Go back to the device's remembered cursor
+    RestoreCursor,
+    /// Send the device the position of its cursor, which it asked for
+    CursorPositionReport,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum View {
+    Text,
+    Hex,
+}
+
+/// How control characters are shown in the text view, raw leaves them to the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlChars {
+    Raw,
+    /// `^C`
+    Caret,
+    /// Unicode control pictures, `␃`
+    Pictures,
+    /// `<0x03>`
+    Hex,
+}
+
+impl ControlChars {
+    pub fn parse(name: &str) -> Option<ControlChars> {
+        match name.to_lowercase().as_str() {
+            "raw" => Some(ControlChars::Raw),
+            "caret" => Some(ControlChars::Caret),
+            "pictures" => Some(ControlChars::Pictures),
+            "hex" => Some(ControlChars::Hex),
+            _ => None,
+        }
+    }
+
+    /// The visible form of a control character, or None if it is printable or shown raw.
+    pub fn marker(&self, character: char) -> Option<String> {
+        if !character.is_control() {
+            return None;
+        }
+        let code = u32::from(character);
+        match (self, code) {
+            (ControlChars::Raw, _) => None,
+            (ControlChars::Caret, 0x00..=0x1f) => {
+                Some(format!("^{}", char::from_u32(code + 0x40).unwrap_or('?')))
+            }
+            (ControlChars::Caret, 0x7f) => Some(String::from("^?")),
+            (ControlChars::Pictures, 0x00..=0x1f) => {
+                Some(String::from(char::from_u32(0x2400 + code).unwrap_or('?')))
+            }
+            (ControlChars::Pictures, 0x7f) => Some(String::from('\u{2421}')),
+            // C1 controls have neither a caret form nor pictures
+            (_, _) => Some(format!("<0x{:02X}>", code)),
+        }
+    }
+}
+
+impl Display for ControlChars {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let name = match self {
+            ControlChars::Raw => "raw",
+            ControlChars::Caret => "caret",
+            ControlChars::Pictures => "pictures",
+            ControlChars::Hex => "hex",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub struct RxDisplay {
+    pub timestamps: bool,
+    pub rx_eol: LineEnding,
+    view: View,
+    control_chars: ControlChars,
+    at_line_start: bool,
+    /// The last character was a CR, so an LF completes a CRLF
+    after_cr: bool,
+    escape_parser: EscapeParser,
+    decoder: Decoder,
+    highlight_rules: Vec<HighlightRule>,
+    /// The text of the current line, for matching the highlight rules
+    line: String,
+    /// Bytes of the line already output, the rest is held back to be printed highlighted
+    printed: usize,
+    /// When the held back part of the line is shown even though the line is incomplete
+    flush_at: Option<Instant>,
+    /// The line has only printable characters, so its text is what is on screen
+    line_is_plain: bool,
+    hex_offset: usize,
+    hex_row: Vec<u8>,
+    /// Output owed from a view change, rendered ahead of the next received byte
+    pending: Vec<RxOutput>,
+}
+
+impl RxDisplay {
+    pub fn new(
+        timestamps: bool,
+        encoding: Encoding,
+        rx_eol: LineEnding,
+        control_chars: ControlChars,
+        highlight_rules: Vec<HighlightRule>,
+    ) -> RxDisplay {
+        RxDisplay {
+            timestamps,
+            rx_eol,
+            view: View::Text,
+            control_chars,
+            at_line_start: true,
+            after_cr: false,
+            escape_parser: EscapeParser::new(),
+            decoder: Decoder::new(encoding),
+            highlight_rules,
+            line: String::new(),
+            printed: 0,
+            flush_at: None,
+            line_is_plain: true,
+            hex_offset: 0,
+            hex_row: Vec::with_capacity(HEX_ROW_LEN),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn set_view(&mut self, view: View) {
+        if view == self.view {
+            return;
+        }
+        let mut pending = std::mem::take(&mut self.pending);
+        self.release_line(&mut pending);
+        self.pending = pending;
+        match view {
+            View::Hex => {
+                if !self.at_line_start {
+                    self.pending.push(RxOutput::Print(String::from("\r")));
+                    self.pending.push(RxOutput::Newline);
+                }
+                self.escape_parser.reset();
+                self.decoder.flush();
+                self.hex_offset = 0;
+            }
+            View::Text => {
+                if !self.hex_row.is_empty() {
+                    self.pending.push(RxOutput::Print(self.hex_ascii_column()));
+                    self.pending.push(RxOutput::Print(String::from("\r")));
+                    self.pending.push(RxOutput::Newline);
+                    self.hex_row.clear();
+                }
+            }
+        }
+        self.at_line_start = true;
+        self.line.clear();
+        self.printed = 0;
+        self.line_is_plain = true;
+        self.view = view;
+    }
+
+    pub fn set_control_chars(&mut self, control_chars: ControlChars) {
+        self.escape_parser.reset();
+        self.control_chars = control_chars;
+    }
+
+    /// Output owed from a view change, for showing it straight away.
+    pub fn take_pending(&mut self) -> Vec<RxOutput> {
+        self.pending.drain(..).collect()
+    }
+
+    /// When the held back part of the line is due to be shown.
+    pub fn flush_deadline(&self) -> Option<Instant> {
+        self.flush_at
+    }
+
+    /// The held back part of the line, once its deadline has passed.
+    pub fn flush(&mut self) -> Vec<RxOutput> {
+        let mut output = Vec::new();
+        self.release_line(&mut output);
+        output
+    }
+
+    pub fn render(&mut self, byte: u8) -> Vec<RxOutput> {
+        let mut output: Vec<RxOutput> = self.pending.drain(..).collect();
+        match self.view {
+            View::Text => self.render_text(byte, &mut output),
+            View::Hex => self.render_hex(byte, &mut output),
+        };
+        output
+    }
+
+    fn timestamp(&self) -> String {
+        format!(
+            "[{}] ",
+            chrono::offset::Local::now().format(TIMESTAMP_FORMAT)
+        )
+    }
+
+    fn render_text(&mut self, byte: u8, output: &mut Vec<RxOutput>) {
+        // escape sequences are only interpreted when control characters are raw
+        let token = match self.control_chars {
+            ControlChars::Raw => self.escape_parser.parse(byte),
+            _ => Some(Token::Byte(byte)),
+        };
+        match token {
+            Some(Token::Byte(byte)) => {
+                for character in self.decoder.decode(byte).chars() {
+                    self.render_char(character, output);
+                }
+            }
+            Some(Token::Sequence(sequence)) => {
+                self.render_sequence(RxOutput::Print(sequence), output);
+            }
+            Some(Token::ClearScreen) => self.render_sequence(RxOutput::ClearScreen, output),
+            Some(Token::ClearBelow) => self.render_sequence(RxOutput::ClearBelow, output),
+            Some(Token::CursorPosition(row, col)) => {
+                self.render_sequence(RxOutput::MoveTo(row - 1, col - 1), output);
+            }
+            Some(Token::SaveCursor) => self.render_sequence(RxOutput::SaveCursor, output),
+            Some(Token::RestoreCursor) => self.render_sequence(RxOutput::RestoreCursor, output),
+            Some(Token::CursorPositionRequest) => output.push(RxOutput::CursorPositionReport),
+            None => {}
+        }
+    }
+
+    /// Output for an escape sequence, after any partial character before it.
+    fn render_sequence(&mut self, sequence: RxOutput, output: &mut Vec<RxOutput>) {
+        if let Some(character) = self.decoder.flush() {
+            self.render_char(character, output);
+        }
+        self.start_line(output);
+        self.release_line(output);
+        self.line_is_plain = false;
+        output.push(sequence);
+    }
+
+    fn render_char(&mut self, character: char, output: &mut Vec<RxOutput>) {
+        let after_cr = std::mem::replace(&mut self.after_cr, character == '\r');
+        let marker = self.control_chars.marker(character);
+        if let Some(marker) = &marker {
+            // shown ahead of any line break it causes
+            self.start_line(output);
+            self.push_text(marker, output);
+        }
+        match (self.rx_eol, character) {
+            // the LF of a CRLF, the CR already ended the line
+            (LineEnding::Auto, '\n') if after_cr => {}
+            (LineEnding::Cr | LineEnding::Auto, '\r')
+            | (LineEnding::Lf | LineEnding::Auto, '\n') => {
+                self.end_line(output);
+                output.push(RxOutput::Print(String::from("\r")));
+                output.push(RxOutput::Newline);
+            }
+            (_, '\n') => {
+                self.end_line(output);
+                if marker.is_some() {
+                    // the CR before it was only shown, not sent to the terminal
+                    output.push(RxOutput::Print(String::from("\r")));
+                }
+                output.push(RxOutput::Newline);
+            }
+            _ if marker.is_some() => {}
+            _ => {
+                if character != '\r' {
+                    self.start_line(output);
+                }
+                if after_cr || character.is_control() {
+                    // held back text goes before the cursor moves
+                    self.release_line(output);
+                }
+                if (after_cr || character.is_control()) && character != '\r' {
+                    // overwritten or moved within the line, its text is no longer what is shown
+                    self.line_is_plain = false;
+                }
+                match character.is_control() {
+                    true => output.push(RxOutput::Print(String::from(character))),
+                    false => self.push_text(character.encode_utf8(&mut [0; 4]), output),
+                }
+            }
+        }
+    }
+
+    /// Add text to the line, holding it back while there are rules to highlight it with.
+    fn push_text(&mut self, text: &str, output: &mut Vec<RxOutput>) {
+        self.line.push_str(text);
+        match !self.highlight_rules.is_empty() && self.line_is_plain {
+            true => {
+                self.flush_at
+                    .get_or_insert_with(|| Instant::now() + HELD_LINE_DELAY);
+            }
+            false => {
+                output.push(RxOutput::Print(text.to_string()));
+                self.printed = self.line.len();
+            }
+        }
+    }
+
+    /// Output the held back part of the line, highlighted.
+    fn release_line(&mut self, output: &mut Vec<RxOutput>) {
+        if self.printed < self.line.len() {
+            let text = highlight(&self.highlight_rules, &self.line, self.printed);
+            output.push(RxOutput::Print(text));
+            self.printed = self.line.len();
+        }
+        self.flush_at = None;
+    }
+
+    /// Output the rest of the completed line.
+    fn end_line(&mut self, output: &mut Vec<RxOutput>) {
+        self.release_line(output);
+        self.at_line_start = true;
+        self.line.clear();
+        self.printed = 0;
+        self.line_is_plain = true;
+    }
+
+    /// Timestamp the line if it is just beginning.
+    fn start_line(&mut self, output: &mut Vec<RxOutput>) {
+        if self.at_line_start && self.timestamps {
+            output.push(RxOutput::Print(self.timestamp()));
+        }
+        self.at_line_start = false;
+    }
+
+    /// Print each byte as it arrives, completing the row with the ASCII column after 16 bytes:
+    /// `00000010  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0d 0a 00  |Hello, world!...|`
+    fn render_hex(&mut self, byte: u8, output: &mut Vec<RxOutput>) {
+        if self.hex_row.is_empty() {
+            if self.timestamps {
+                output.push(RxOutput::Print(self.timestamp()));
+            }
+            output.push(RxOutput::Print(format!("{:08x}  ", self.hex_offset)));
+            self.at_line_start = false;
+        }
+
+        self.hex_row.push(byte);
+        self.hex_offset += 1;
+        match self.hex_row.len() {
+            8 => output.push(RxOutput::Print(format!("{:02x}  ", byte))),
+            _ => output.push(RxOutput::Print(format!("{:02x} ", byte))),
+        };
+
+        if self.hex_row.len() == HEX_ROW_LEN {
+            output.push(RxOutput::Print(self.hex_ascii_column()));
+            output.push(RxOutput::Print(String::from("\r")));
+            output.push(RxOutput::Newline);
+            self.hex_row.clear();
+            self.at_line_start = true;
+        }
+    }
+
+    /// Pad a partial row out to full width and print its bytes as ASCII.
+    fn hex_ascii_column(&self) -> String {
+        let missing = HEX_ROW_LEN - self.hex_row.len();
+        let mut padding = " ".repeat(missing * 3);
+        if self.hex_row.len() < 8 {
+            padding.push(' ');
+        }
+        let ascii: String = self
+            .hex_row
+            .iter()
+            .map(|&c| match c {
+                0x20..=0x7e => c as char,
+                _ => '.',
+            })
+            .collect();
+        format!("{} |{}|", padding, ascii)
+    }
+}
+
+/// Handle the menu command `ts on` or `ts off`, returning a message for the UI.
+pub fn timestamps_command(rx_display: &mut RxDisplay, argument: &str) -> String {
+    match argument {
+        "on" => rx_display.timestamps = true,
+        "off" => rx_display.timestamps = false,
+        _ => return String::from("Usage: ts [on|off]"),
+    };
+    format!("Timestamps {}", argument)
+}
+
+/// Handle the menu command `view hex` or `view text`, returning a message for the UI.
+pub fn view_command(rx_display: &mut RxDisplay, argument: &str) -> String {
+    match argument {
+        "hex" => rx_display.set_view(View::Hex),
+        "text" => rx_display.set_view(View::Text),
+        _ => return String::from("Usage: view hex|text"),
+    };
+    format!("Showing received data as {}", argument)
+}
+
+/// Handle the menu command `control raw|caret|pictures|hex`, returning a message for the UI.
+pub fn control_chars_command(rx_display: &mut RxDisplay, argument: &str) -> String {
+    match ControlChars::parse(argument) {
+        Some(control_chars) => {
+            rx_display.set_control_chars(control_chars);
+            format!("Showing control characters as {}", control_chars)
+        }
+        None => String::from("Usage: control raw|caret|pictures|hex"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::History;
+    use crate::line_filter::{filter_command, LineFilter};
+
+    /// The text printed for the outputs, with newlines as `\n`.
+    fn printed(outputs: Vec<RxOutput>) -> String {
+        outputs
+            .into_iter()
+            .map(|output| match output {
+                RxOutput::Print(text) => text,
+                RxOutput::Newline => String::from("\n"),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    fn render(rx_display: &mut RxDisplay, bytes: &[u8]) -> String {
+        printed(
+            bytes
+                .iter()
+                .flat_map(|&byte| rx_display.render(byte))
+                .collect(),
+        )
+    }
+
+    fn plain(rx_eol: LineEnding, control_chars: ControlChars) -> RxDisplay {
+        RxDisplay::new(false, Encoding::Utf8, rx_eol, control_chars, vec![])
+    }
+
+    fn highlighting(rule: &str) -> RxDisplay {
+        let rules = vec![HighlightRule::parse(rule).unwrap()];
+        RxDisplay::new(
+            false,
+            Encoding::Utf8,
+            LineEnding::Lf,
+            ControlChars::Raw,
+            rules,
+        )
+    }
+
+    #[test]
+    fn prints_as_received_without_rules() {
+        let mut rx_display = RxDisplay::new(
+            false,
+            Encoding::Utf8,
+            LineEnding::Lf,
+            ControlChars::Raw,
+            vec![],
+        );
+        assert_eq!(render(&mut rx_display, b"ab"), "ab");
+        assert!(rx_display.flush_deadline().is_none());
+    }
+
+    #[test]
+    fn holds_lines_back_to_highlight_them() {
+        let mut rx_display = highlighting("ERR=red");
+        assert_eq!(render(&mut rx_display, b"ERR"), "");
+        assert!(rx_display.flush_deadline().is_some());
+        assert_eq!(
+            render(&mut rx_display, b"\n"),
+            format!("{}\r\n", highlight(&rx_display.highlight_rules, "ERR", 0))
+        );
+        assert!(rx_display.flush_deadline().is_none());
+    }
+
+    #[test]
+    fn shows_a_held_prompt_once_flushed() {
+        let mut rx_display = highlighting("ERR=red");
+        assert_eq!(render(&mut rx_display, b"$ "), "");
+        assert_eq!(printed(rx_display.flush()), "$ ");
+        assert_eq!(render(&mut rx_display, b"ls\n"), "ls\r\n");
+    }
+
+    #[test]
+    fn highlighted_lines_keep_their_text_for_history_and_filter() {
+        let mut rx_display = highlighting("ERR=red");
+        let mut history = History::new(10);
+        let mut line_filter = LineFilter::new();
+        filter_command(&mut line_filter, "boot");
+
+        let mut shown = Vec::new();
+        for &byte in b"ERR boot failed\nx ERR boot\nERR other\n" {
+            let outputs = rx_display.render(byte);
+            for output in &outputs {
+                history.push(output);
+            }
+            shown.extend(line_filter.apply(outputs));
+        }
+
+        assert_eq!(history.line(0), "ERR boot failed");
+        assert_eq!(history.line(1), "x ERR boot");
+        assert_eq!(history.line(2), "ERR other");
+        let red = |text: &str| highlight(&rx_display.highlight_rules, text, 0);
+        assert_eq!(
+            printed(shown),
+            format!("{}\r\n{}\r\n", red("ERR boot failed"), red("x ERR boot"))
+        );
+    }
+
+    #[test]
+    fn prints_the_rest_of_an_overwritten_line_as_received() {
+        let mut rx_display = highlighting("ERR=red");
+        assert_eq!(render(&mut rx_display, b"ab\r"), "ab\r");
+        assert_eq!(render(&mut rx_display, b"c"), "c");
+    }
+
+    #[test]
+    fn ends_lines_on_any_ending_in_auto() {
+        let mut rx_display = plain(LineEnding::Auto, ControlChars::Raw);
+        assert_eq!(
+            render(&mut rx_display, b"a\r\nb\rc\nd\n\r"),
+            "a\r\nb\r\nc\r\nd\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn ends_lines_on_cr_in_cr_mode() {
+        let mut rx_display = plain(LineEnding::Cr, ControlChars::Raw);
+        // an LF on its own only moves down
+        assert_eq!(render(&mut rx_display, b"a\rb\nc\r"), "a\r\nb\nc\r\n");
+    }
+
+    #[test]
+    fn marks_control_characters() {
+        let marks = |control_chars: ControlChars| {
+            ['a', '\0', '\x03', '\x1b', '\x7f', '\u{9b}']
+                .map(|character| control_chars.marker(character))
+        };
+        let some = |marker: &str| Some(String::from(marker));
+        assert_eq!(
+            marks(ControlChars::Raw),
+            [None, None, None, None, None, None]
+        );
+        assert_eq!(
+            marks(ControlChars::Caret),
+            [
+                None,
+                some("^@"),
+                some("^C"),
+                some("^["),
+                some("^?"),
+                some("<0x9B>")
+            ]
+        );
+        assert_eq!(
+            marks(ControlChars::Pictures),
+            [
+                None,
+                some("␀"),
+                some("␃"),
+                some("␛"),
+                some("␡"),
+                some("<0x9B>")
+            ]
+        );
+        assert_eq!(
+            marks(ControlChars::Hex),
+            [
+                None,
+                some("<0x00>"),
+                some("<0x03>"),
+                some("<0x1B>"),
+                some("<0x7F>"),
+                some("<0x9B>")
+            ]
+        );
+    }
+
+    #[test]
+    fn shows_markers_ahead_of_the_line_break() {
+        let mut rx_display = plain(LineEnding::Auto, ControlChars::Caret);
+        assert_eq!(render(&mut rx_display, b"a\x1b[m\r\nb"), "a^[[m^M\r\n^Jb");
+    }
+
+    #[test]
+    fn lays_out_hex_rows() {
+        let mut rx_display = plain(LineEnding::Lf, ControlChars::Raw);
+        view_command(&mut rx_display, "hex");
+        assert_eq!(
+            render(&mut rx_display, b"Hello, world!\r\n\0abc"),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0d 0a 00  |Hello, world!...|\r\n\
+             00000010  61 62 63 "
+        );
+        // a partial row is padded to line its ASCII column up with the full rows
+        view_command(&mut rx_display, "text");
+        assert_eq!(
+            printed(rx_display.take_pending()),
+            format!("{} |abc|\r\n", " ".repeat(40))
+        );
+    }
+
+    #[test]
+    fn starts_hex_rows_on_a_new_line() {
+        let mut rx_display = plain(LineEnding::Lf, ControlChars::Raw);
+        assert_eq!(render(&mut rx_display, b"ab"), "ab");
+        view_command(&mut rx_display, "hex");
+        assert_eq!(render(&mut rx_display, b"c"), "\r\n00000000  63 ");
+    }
+}
@@ -0,0 +1,47 @@
+use crossterm::{
+    cursor, queue,
+    style::Print,
+    terminal::{size, Clear, ClearType::FromCursorDown},
+};
+
+use crate::cast::stdout;
+
+/// Limit scrolling to the rows above the bottom `count` rows, which are left for panes and
+/// bars, scrolling the screen up first if the cursor is in them. Called again after a resize.
+pub fn reserve_bottom_rows(count: u16) {
+    let (_, rows) = size().unwrap();
+    let (_, row) = cursor::position().unwrap();
+    let last_scrolling_row = rows.saturating_sub(count + 1);
+    let mut stdout = stdout();
+    if row > last_scrolling_row {
+        let scroll = row - last_scrolling_row;
+        queue!(
+            stdout,
+            Print("\n".repeat(scroll.into())),
+            cursor::MoveUp(scroll)
+        )
+        .unwrap();
+    }
+    // setting the scrolling region moves the cursor home
+    queue!(
+        stdout,
+        cursor::SavePosition,
+        Print(format!("\x1b[1;{}r", last_scrolling_row + 1)),
+        cursor::RestorePosition,
+    )
+    .unwrap();
+}
+
+/// Let the whole screen scroll again, clearing the bottom `count` rows.
+pub fn release(count: u16) {
+    let (_, rows) = size().unwrap();
+    queue!(
+        stdout(),
+        cursor::SavePosition,
+        Print("\x1b[r"),
+        cursor::MoveTo(0, rows.saturating_sub(count)),
+        Clear(FromCursorDown),
+        cursor::RestorePosition
+    )
+    .unwrap();
+}
@@ -1,5 +1,5 @@
 use std::{
-    io::{stdout, ErrorKind, Write},
+    io::{ErrorKind, Write},
     time::Duration,
 };
 
@@ -11,6 +11,8 @@ use crossterm::{
 use terminal_spinner_data::{SpinnerData, DOTS12};
 use tokio_serial::{SerialPortBuilderExt, SerialStream};
 
+use crate::cast::stdout;
+use crate::event_log::{log_event, Event};
 use crate::log_to_ui::{log_to_ui, print_log_to_stdout};
 use crate::Args;
 
@@ -34,17 +36,19 @@ pub async fn wait_for_serial_port(args: &Args, error_kind: Option<ErrorKind>) ->
                     MoveUp(1),
                 )
                 .unwrap();
-                log_to_ui!("Connected to {}", args.port);
+                log_to_ui!("Connected to {}", args.port());
+                log_event(Event::Connected);
                 queue!(stdout, Show).unwrap();
                 break serial_conn;
             }
             None => {
                 if is_first_retry {
+                    log_event(Event::Waiting(error_kind));
                     match error_kind {
                         Some(error_kind) => {
-                            log_to_ui!("{} error '{}', waiting", args.port, error_kind)
+                            log_to_ui!("{} error '{}', waiting", args.port(), error_kind)
                         }
-                        None => log_to_ui!("Waiting for {}", args.port),
+                        None => log_to_ui!("Waiting for {}", args.port()),
                     };
                     queue!(stdout, Print(frame), Hide).unwrap();
                     is_first_retry = false;
@@ -72,7 +76,7 @@ pub async fn wait_for_serial_port(args: &Args, error_kind: Option<ErrorKind>) ->
 }
 
 fn get_serial_connection(args: &Args) -> Option<SerialStream> {
-    let mut serial_connection_res = tokio_serial::new(args.port.clone(), args.baud)
+    let mut serial_connection_res = tokio_serial::new(args.port(), args.baud)
         .data_bits(args.data_bits.value)
         .flow_control(args.flow_control.value)
         .parity(args.parity.value)
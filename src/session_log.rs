@@ -0,0 +1,125 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::rotation::{RotatingFile, Rotation};
+
+/// Received bytes are written out once this many are waiting, or after `FLUSH_INTERVAL`.
+const FLUSH_SIZE: usize = 4096;
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A capture of every byte received from the serial port, kept open across reconnects.
+pub struct SessionLog {
+    file: Option<RotatingFile>,
+    pub path: Option<PathBuf>,
+    rotation: Rotation,
+    /// Bytes not yet written, and when they are due to be
+    pending: Vec<u8>,
+    flush_at: Option<Instant>,
+}
+
+impl SessionLog {
+    pub fn new(rotation: Rotation) -> SessionLog {
+        SessionLog {
+            file: None,
+            path: None,
+            rotation,
+            pending: Vec::with_capacity(FLUSH_SIZE),
+            flush_at: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Open `path` for appending, closing any log that is already active.
+    pub fn start(&mut self, path: &Path) -> io::Result<()> {
+        self.stop();
+        self.file = Some(RotatingFile::append(path, self.rotation)?);
+        self.path = Some(path.to_path_buf());
+        self.mark("Log started")
+    }
+
+    pub fn stop(&mut self) {
+        if self.is_active() {
+            // the file is closed regardless, a failed final mark has nowhere to go
+            let _ = self.mark("Log stopped");
+        }
+        self.pending.clear();
+        self.flush_at = None;
+        self.file = None;
+        self.path = None;
+    }
+
+    /// Add bytes to the log, which are written out in chunks.
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.file.is_none() {
+            return Ok(());
+        }
+        if self.pending.is_empty() {
+            self.flush_at = Some(Instant::now() + FLUSH_INTERVAL);
+        }
+        self.pending.extend_from_slice(bytes);
+        match self.pending.len() >= FLUSH_SIZE {
+            true => self.flush(),
+            false => Ok(()),
+        }
+    }
+
+    /// When the waiting bytes are due to be written, if there are any.
+    pub fn flush_deadline(&self) -> Option<Instant> {
+        self.flush_at
+    }
+
+    /// Write out the waiting bytes as one chunk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.flush_at = None;
+        let pending = std::mem::take(&mut self.pending);
+        match &mut self.file {
+            Some(file) if !pending.is_empty() => file.write_all(&pending),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write a timestamped event, such as a disconnect, inline with the received data.
+    pub fn mark(&mut self, msg: &str) -> io::Result<()> {
+        self.write(
+            format!(
+                "\r\n[{}] {}\r\n",
+                chrono::offset::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                msg
+            )
+            .as_bytes(),
+        )?;
+        self.flush()
+    }
+}
+
+impl Drop for SessionLog {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Handle the menu command `log start <path>` or `log stop`, returning a message for the UI.
+pub fn log_command(session_log: &mut SessionLog, argument: &str) -> String {
+    match argument.split_once(' ') {
+        Some(("start", path)) => match session_log.start(Path::new(path.trim())) {
+            Ok(_) => format!("Logging to {}", path.trim()),
+            Err(error) => format!("Unable to log to {}: {}", path.trim(), error),
+        },
+        None if argument == "stop" => match session_log.path.take() {
+            Some(path) => {
+                session_log.stop();
+                format!("Stopped logging to {}", path.display())
+            }
+            None => String::from("Not logging"),
+        },
+        _ => String::from("Usage: log start <path> | log stop"),
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use tokio_serial::{DataBits, Parity, StopBits};
+
+use crate::Args;
+
+/// Seconds of throughput kept for the rates and the sparkline.
+const SECONDS_KEPT: usize = 60;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Bytes and lines in one second.
+#[derive(Clone, Copy, Default)]
+struct Second {
+    rx_bytes: u64,
+    tx_bytes: u64,
+    rx_lines: u64,
+}
+
+/// Counters for the session, kept whether or not the `stats` view is open.
+pub struct Stats {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    rx_lines: u64,
+    rx_errors: u64,
+    reconnects: u64,
+    connections: u64,
+    started: Instant,
+    /// The most bytes a second the link can carry, from the baud rate and character frame
+    link_bytes_per_second: f64,
+    /// Counts for the second in progress, and the completed seconds, newest last
+    current: Second,
+    current_started: Instant,
+    seconds: VecDeque<Second>,
+    peak: Second,
+}
+
+impl Stats {
+    pub fn new(args: &Args) -> Stats {
+        let data_bits = match args.data_bits.value {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity_bits = u32::from(args.parity.value != Parity::None);
+        let stop_bits = match args.stop_bits.value {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        // a start bit and the rest of the frame for each byte
+        let frame_bits = 1 + data_bits + parity_bits + stop_bits;
+        Stats {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            rx_lines: 0,
+            rx_errors: 0,
+            reconnects: 0,
+            connections: 0,
+            started: Instant::now(),
+            link_bytes_per_second: f64::from(args.baud) / f64::from(frame_bits),
+            current: Second::default(),
+            current_started: Instant::now(),
+            seconds: VecDeque::with_capacity(SECONDS_KEPT),
+            peak: Second::default(),
+        }
+    }
+
+    pub fn rx(&mut self, bytes: u64, lines: u64) {
+        self.catch_up();
+        self.rx_bytes += bytes;
+        self.rx_lines += lines;
+        self.current.rx_bytes += bytes;
+        self.current.rx_lines += lines;
+    }
+
+    pub fn tx(&mut self, bytes: u64) {
+        self.catch_up();
+        self.tx_bytes += bytes;
+        self.current.tx_bytes += bytes;
+    }
+
+    pub fn rx_error(&mut self) {
+        self.rx_errors += 1;
+    }
+
+    /// Every connection after the first is a reconnect.
+    pub fn connected(&mut self) {
+        if self.connections > 0 {
+            self.reconnects += 1;
+        }
+        self.connections += 1;
+    }
+
+    /// End the seconds that have passed since the one in progress began, so that the counts
+    /// need no timer while nothing shows them.
+    fn catch_up(&mut self) {
+        let elapsed = self.current_started.elapsed().as_secs();
+        for _ in 0..elapsed.min(SECONDS_KEPT as u64) {
+            self.end_second();
+        }
+        self.current_started += Duration::from_secs(elapsed);
+    }
+
+    fn end_second(&mut self) {
+        let second = std::mem::take(&mut self.current);
+        self.peak.rx_bytes = self.peak.rx_bytes.max(second.rx_bytes);
+        self.peak.tx_bytes = self.peak.tx_bytes.max(second.tx_bytes);
+        self.peak.rx_lines = self.peak.rx_lines.max(second.rx_lines);
+        if self.seconds.len() == SECONDS_KEPT {
+            self.seconds.pop_front();
+        }
+        self.seconds.push_back(second);
+    }
+
+    fn last_second(&self) -> Second {
+        self.seconds.back().copied().unwrap_or_default()
+    }
+
+    /// How much of the link a rate uses.
+    fn link_use(&self, bytes_per_second: u64) -> String {
+        format!(
+            "{:.0}%",
+            bytes_per_second as f64 * 100.0 / self.link_bytes_per_second
+        )
+    }
+
+    /// RX bytes a second over the kept seconds, scaled to the busiest.
+    fn sparkline(&self) -> String {
+        let busiest = self
+            .seconds
+            .iter()
+            .map(|second| second.rx_bytes)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        // padded to a fixed width so that the view does not change size
+        let padding = " ".repeat(SECONDS_KEPT - self.seconds.len());
+        let sparks: String = self
+            .seconds
+            .iter()
+            .map(|second| {
+                let level = second.rx_bytes * (SPARKS.len() as u64 - 1) / busiest;
+                SPARKS[level as usize]
+            })
+            .collect();
+        padding + &sparks
+    }
+
+    /// The stats as lines of text for the view.
+    pub fn lines(&mut self) -> Vec<String> {
+        self.catch_up();
+        let secs = self.started.elapsed().as_secs();
+        let last = self.last_second();
+        vec![
+            format!(
+                "Elapsed       {:02}:{:02}:{:02}",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            ),
+            String::new(),
+            format!("{:12}  {:>12}  {:>12}", "", "RX", "TX"),
+            format!(
+                "{:12}  {:>12}  {:>12}",
+                "Total bytes", self.rx_bytes, self.tx_bytes
+            ),
+            format!(
+                "{:12}  {:>12}  {:>12}",
+                "Bytes/s", last.rx_bytes, last.tx_bytes
+            ),
+            format!(
+                "{:12}  {:>12}  {:>12}",
+                "Peak bytes/s", self.peak.rx_bytes, self.peak.tx_bytes
+            ),
+            format!(
+                "{:12}  {:>12}  {:>12}",
+                "Link use",
+                self.link_use(last.rx_bytes),
+                self.link_use(last.tx_bytes)
+            ),
+            format!(
+                "{:12}  {:>12}  {:>12}",
+                "Peak use",
+                self.link_use(self.peak.rx_bytes),
+                self.link_use(self.peak.tx_bytes)
+            ),
+            String::new(),
+            format!(
+                "RX lines      {} total, {}/s, peak {}/s",
+                self.rx_lines, last.rx_lines, self.peak.rx_lines
+            ),
+            format!("RX errors     {}", self.rx_errors),
+            format!("Reconnects    {}", self.reconnects),
+            String::new(),
+            format!("RX last {}s", self.seconds.len()),
+            self.sparkline(),
+        ]
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Instant;
+
+use crossterm::{
+    cursor, queue,
+    style::{
+        Color::{DarkGrey, Reset, White},
+        Print, SetBackgroundColor, SetForegroundColor,
+    },
+    terminal::size,
+};
+use tokio_serial::{Parity, StopBits};
+
+use crate::cast::stdout;
+use crate::Args;
+
+/// The bottom row of the screen, kept out of the scrolling region so received data never
+/// overwrites it.
+pub struct StatusBar {
+    /// Port and settings, e.g. `/dev/ttyUSB0 115200 8N1 flow none`
+    settings: String,
+    connected_since: Option<Instant>,
+}
+
+impl StatusBar {
+    pub fn new(args: &Args) -> StatusBar {
+        let parity = match args.parity.value {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop_bits = match args.stop_bits.value {
+            StopBits::One => '1',
+            StopBits::Two => '2',
+        };
+        StatusBar {
+            settings: format!(
+                "{} {} {}{}{} flow {}",
+                args.port(),
+                args.baud,
+                args.data_bits,
+                parity,
+                stop_bits,
+                args.flow_control
+            ),
+            connected_since: None,
+        }
+    }
+
+    pub fn connected(&mut self) {
+        self.connected_since = Some(Instant::now());
+    }
+
+    pub fn disconnected(&mut self) {
+        self.connected_since = None;
+    }
+}
+
+fn elapsed(since: Instant) -> String {
+    let secs = since.elapsed().as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+pub fn draw(status_bar: &StatusBar, rx_bytes: u64, tx_bytes: u64, log_path: Option<String>) {
+    let (cols, rows) = size().unwrap();
+    let connection = match status_bar.connected_since {
+        Some(since) => format!("connected {}", elapsed(since)),
+        None => String::from("waiting"),
+    };
+    let logging = match log_path {
+        Some(path) => format!("logging to {}", path),
+        None => String::from("not logging"),
+    };
+    let status = format!(
+        " {} | {} | RX {} TX {} | {}",
+        status_bar.settings, connection, rx_bytes, tx_bytes, logging
+    );
+    let status: String = status.chars().take(cols.into()).collect();
+    let blank_row: String = vec![" "; cols.into()].into_iter().collect();
+
+    queue!(
+        stdout(),
+        cursor::SavePosition,
+        cursor::MoveTo(0, rows - 1),
+        SetBackgroundColor(DarkGrey),
+        SetForegroundColor(White),
+        Print(blank_row),
+        cursor::MoveTo(0, rows - 1),
+        Print(status),
+        SetBackgroundColor(Reset),
+        SetForegroundColor(Reset),
+        cursor::RestorePosition,
+    )
+    .unwrap();
+}
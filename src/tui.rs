@@ -0,0 +1,331 @@
+//! Full-screen layout for `--tui` on the alternate screen: received data scrolls in the output
+//! area above the sent data pane, a fixed command line and the status bar, and popups are drawn
+//! over the output area.
+
+use std::io::Write;
+
+use crossterm::{
+    cursor,
+    event::{read, Event as CrosstermEvent, KeyCode},
+    queue,
+    style::{
+        Color::{Black, DarkGrey, Reset, White},
+        Print, SetBackgroundColor, SetForegroundColor,
+    },
+    terminal::{size, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::cast::stdout;
+use crate::encoding::Encoding;
+use crate::highlight::HighlightRule;
+use crate::history::History;
+use crate::keyboard_input::escape_key_name;
+use crate::line_ending::LineEnding;
+use crate::line_filter::LineFilter;
+use crate::list_ports::port_descriptions;
+use crate::menu::{self, MenuState, COMMAND_HELP};
+use crate::rx_display::{ControlChars, RxDisplay, RxOutput};
+
+/// A box over the output area, closed with Esc, q, or Enter.
+pub struct Popup {
+    title: String,
+    lines: Vec<String>,
+    /// The highlighted line, for popups that pick one
+    selected: Option<usize>,
+}
+
+impl Popup {
+    pub fn new(title: &str, lines: Vec<String>) -> Popup {
+        Popup {
+            title: String::from(title),
+            lines,
+            selected: None,
+        }
+    }
+}
+
+pub fn enter() {
+    queue!(
+        stdout(),
+        EnterAlternateScreen,
+        Clear(ClearType::All),
+        cursor::MoveTo(0, 0)
+    )
+    .unwrap();
+}
+
+pub fn leave() {
+    queue!(stdout(), LeaveAlternateScreen).unwrap();
+}
+
+/// Draw the open menu on the command line at `row`, or a hint when it is closed.
+pub fn draw_command_line(menu_state: MenuState, row: u16) -> MenuState {
+    if menu_state.is_open {
+        return menu::open_at_row(menu_state, row);
+    }
+    draw_hint(row);
+    menu_state
+}
+
+/// The command line at `row` while the menu is closed.
+pub fn draw_hint(row: u16) {
+    queue!(
+        stdout(),
+        cursor::SavePosition,
+        cursor::MoveTo(0, row),
+        Clear(ClearType::CurrentLine),
+        SetForegroundColor(DarkGrey),
+        Print(format!(
+            " {} for the menu, then help for commands",
+            escape_key_name()
+        )),
+        SetForegroundColor(Reset),
+        cursor::RestorePosition,
+    )
+    .unwrap();
+}
+
+/// Clear the `rows` rows of the output area, returning the output that fills them again with the
+/// last lines received that pass the filter, rendered like received data to be highlighted.
+pub fn redraw_output(
+    history: &History,
+    line_filter: &LineFilter,
+    highlight_rules: &[HighlightRule],
+    rows: u16,
+) -> Vec<RxOutput> {
+    let (cols, _) = size().unwrap();
+    let cols = usize::from(cols.max(1));
+    let last = history.last();
+
+    // the line being received, unless the filter is holding it back
+    let current = match line_filter.shows_partial_line() {
+        true => history.line(last),
+        false => "",
+    };
+
+    // take lines from the end until they fill the area, counting the rows of wrapped lines
+    let mut shown = Vec::new();
+    let mut used_rows = current.chars().count().max(1).div_ceil(cols);
+    for index in (history.first()..last).rev() {
+        if used_rows >= usize::from(rows) {
+            break;
+        }
+        let line = history.line(index);
+        if line_filter.is_shown(line) {
+            used_rows += line.chars().count().max(1).div_ceil(cols);
+            shown.push(line);
+        }
+    }
+    shown.reverse();
+
+    let mut stdout = stdout();
+    for row in 0..rows {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, row),
+            Clear(ClearType::CurrentLine)
+        )
+        .unwrap();
+    }
+
+    // history lines are text without timestamps to add or control characters to show
+    let mut rx_display = RxDisplay::new(
+        false,
+        Encoding::Utf8,
+        LineEnding::Lf,
+        ControlChars::Raw,
+        highlight_rules.to_vec(),
+    );
+    let mut outputs = Vec::new();
+    for line in shown {
+        for byte in line.bytes().chain([b'\n']) {
+            outputs.extend(rx_display.render(byte));
+        }
+    }
+    for byte in current.bytes() {
+        outputs.extend(rx_display.render(byte));
+    }
+    outputs.extend(rx_display.flush());
+    outputs
+}
+
+/// The keys and menu commands, one per line, for the help popup or below the menu.
+pub fn help_lines() -> Vec<String> {
+    let width = COMMAND_HELP
+        .iter()
+        .map(|(command, _)| command.len())
+        .max()
+        .unwrap_or_default();
+    let mut lines = vec![
+        format!("{:width$}  {}", escape_key_name(), "Open the menu"),
+        format!("{:width$}  {}", "Esc", "Close the menu"),
+        format!(
+            "{:width$}  {}",
+            format!("{0} {0}", escape_key_name()),
+            "Send the menu key itself"
+        ),
+        String::new(),
+    ];
+    lines.extend(
+        COMMAND_HELP
+            .iter()
+            .map(|(command, about)| format!("{:width$}  {}", command, about)),
+    );
+    lines
+}
+
+pub fn help_popup() -> Popup {
+    Popup {
+        title: String::from("Help"),
+        lines: help_lines(),
+        selected: None,
+    }
+}
+
+pub fn ports_popup() -> Popup {
+    let ports = port_descriptions();
+    let width = ports
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default();
+    let mut lines: Vec<String> = ports
+        .iter()
+        .map(|(name, about)| format!("{:width$}  {}", name, about))
+        .collect();
+    if lines.is_empty() {
+        lines.push(String::from("No ports found"));
+    }
+    Popup {
+        title: String::from("Ports"),
+        lines,
+        selected: None,
+    }
+}
+
+/// Move the selection with Up and Down, returning None for the keys that close the popup.
+pub fn handle_popup_event(popup: Popup, event: CrosstermEvent) -> Option<Popup> {
+    let selected = match (event, popup.selected) {
+        (CrosstermEvent::Key(key), selected) => match (key.code, selected) {
+            (KeyCode::Esc, _) | (KeyCode::Char('q'), _) | (KeyCode::Enter, _) => return None,
+            (KeyCode::Up, Some(selected)) => Some(selected.saturating_sub(1)),
+            (KeyCode::Down, Some(selected)) => {
+                Some((selected + 1).min(popup.lines.len().saturating_sub(1)))
+            }
+            (_, selected) => selected,
+        },
+        (_, selected) => selected,
+    };
+    Some(Popup { selected, ..popup })
+}
+
+/// Draw the popup in the middle of the top `rows` rows.
+pub fn draw_popup(popup: &Popup, rows: u16) {
+    let (cols, _) = size().unwrap();
+    let inner_width = popup
+        .lines
+        .iter()
+        .map(|line| line.chars().count())
+        .chain([popup.title.chars().count() + 2])
+        .max()
+        .unwrap_or_default()
+        .min(usize::from(cols.saturating_sub(4)));
+    let line_rows = popup.lines.len().min(usize::from(rows.saturating_sub(2)));
+    // keep the selected line in view
+    let first = match popup.selected {
+        Some(selected) => (selected + 1).saturating_sub(line_rows),
+        None => 0,
+    };
+    let left = cols.saturating_sub(inner_width as u16 + 4) / 2;
+    let top = rows.saturating_sub(line_rows as u16 + 2) / 2;
+    let mut stdout = stdout();
+
+    let title = format!(" {} ", popup.title);
+    let top_border = format!(
+        "┌─{}{}─┐",
+        title,
+        "─".repeat(inner_width.saturating_sub(title.chars().count()))
+    );
+    let bottom_border = format!("└{}┘", "─".repeat(inner_width + 2));
+    queue!(
+        stdout,
+        cursor::Hide,
+        SetBackgroundColor(DarkGrey),
+        SetForegroundColor(White),
+        cursor::MoveTo(left, top),
+        Print(top_border),
+    )
+    .unwrap();
+    for row in 0..line_rows {
+        let index = first + row;
+        let line: String = popup.lines[index].chars().take(inner_width).collect();
+        let padding = " ".repeat(inner_width - line.chars().count());
+        queue!(
+            stdout,
+            cursor::MoveTo(left, top + 1 + row as u16),
+            Print("│ ")
+        )
+        .unwrap();
+        if popup.selected == Some(index) {
+            queue!(
+                stdout,
+                SetBackgroundColor(White),
+                SetForegroundColor(Black),
+                Print(format!("{}{}", line, padding)),
+                SetBackgroundColor(DarkGrey),
+                SetForegroundColor(White),
+            )
+            .unwrap();
+        } else {
+            queue!(stdout, Print(format!("{}{}", line, padding))).unwrap();
+        }
+        queue!(stdout, Print(" │")).unwrap();
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(left, top + 1 + line_rows as u16),
+        Print(bottom_border),
+        SetBackgroundColor(Reset),
+        SetForegroundColor(Reset),
+    )
+    .unwrap();
+}
+
+/// Pick a port from a popup before connecting, for when none was given. Returns None if there
+/// are no ports or the popup was closed without picking one.
+pub fn select_port() -> Option<String> {
+    let ports = port_descriptions();
+    if ports.is_empty() {
+        return None;
+    }
+    let mut popup = ports_popup();
+    popup.title = String::from("Select a port");
+    popup.selected = Some(0);
+
+    enter();
+    let selected = loop {
+        let (_, rows) = size().unwrap();
+        queue!(stdout(), Clear(ClearType::All)).unwrap();
+        draw_popup(&popup, rows);
+        stdout().flush().unwrap();
+
+        let event = match read() {
+            Ok(event) => event,
+            Err(_) => break None,
+        };
+        if let CrosstermEvent::Key(key) = &event {
+            if key.code == KeyCode::Enter {
+                break popup.selected;
+            }
+        }
+        popup = match handle_popup_event(popup, event) {
+            Some(popup) => popup,
+            None => break None,
+        };
+    };
+    queue!(stdout(), cursor::Show).unwrap();
+    leave();
+    stdout().flush().unwrap();
+
+    selected.map(|index| ports[index].0.clone())
+}
@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+use crossterm::{
+    cursor, queue,
+    style::{
+        Color::{DarkGrey, Reset, White},
+        Print, SetBackgroundColor, SetForegroundColor,
+    },
+    terminal::{size, Clear, ClearType::CurrentLine},
+};
+
+use crate::cast::stdout;
+use crate::constants::TIMESTAMP_FORMAT;
+use crate::encoding::{Decoder, Encoding};
+use crate::rx_display::ControlChars;
+
+/// Sent lines kept for the pane, more than it can show so that a resize can show more.
+const MAX_LINES: usize = 100;
+
+/// Shows what was sent and when, below the received data, for devices that do not echo.
+pub struct TxPane {
+    lines: VecDeque<String>,
+    /// The line being sent, starting with the time of its first byte
+    current: String,
+    after_cr: bool,
+    /// Sent bytes are in the TX encoding
+    decoder: Decoder,
+}
+
+impl TxPane {
+    pub fn new(tx_encoding: Encoding) -> TxPane {
+        TxPane {
+            lines: VecDeque::with_capacity(MAX_LINES),
+            current: String::new(),
+            after_cr: false,
+            decoder: Decoder::new(tx_encoding),
+        }
+    }
+
+    /// Add sent bytes, each CR or LF ends a line and a CRLF ends just one.
+    pub fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+            // empty until the last byte of a multi-byte character
+            let shown: String = self
+                .decoder
+                .decode(byte)
+                .chars()
+                .map(|character| {
+                    ControlChars::Caret
+                        .marker(character)
+                        .unwrap_or_else(|| String::from(character))
+                })
+                .collect();
+
+            if byte == b'\n' && after_cr {
+                if let Some(line) = self.lines.back_mut() {
+                    line.push_str(&shown);
+                }
+                continue;
+            }
+            if self.current.is_empty() {
+                self.current = format!(
+                    "[{}] ",
+                    chrono::offset::Local::now().format(TIMESTAMP_FORMAT)
+                );
+            }
+            self.current.push_str(&shown);
+            if byte == b'\r' || byte == b'\n' {
+                if self.lines.len() == MAX_LINES {
+                    self.lines.pop_front();
+                }
+                self.lines.push_back(std::mem::take(&mut self.current));
+            }
+        }
+    }
+}
+
+/// Rows taken by the pane, including its title bar.
+pub fn rows() -> u16 {
+    let (_, rows) = size().unwrap();
+    (rows / 4).clamp(3, 12)
+}
+
+/// Draw the pane above the bottom `below` rows, e.g. the status bar.
+pub fn draw(tx_pane: &TxPane, below: u16) {
+    let (cols, screen_rows) = size().unwrap();
+    let pane_rows = rows();
+    let top = screen_rows.saturating_sub(pane_rows + below);
+    let line_rows = usize::from(pane_rows - 1);
+    let blank_row: String = vec![" "; cols.into()].into_iter().collect();
+    let mut stdout = stdout();
+
+    queue!(
+        stdout,
+        cursor::SavePosition,
+        cursor::MoveTo(0, top),
+        SetBackgroundColor(DarkGrey),
+        SetForegroundColor(White),
+        Print(&blank_row),
+        cursor::MoveTo(0, top),
+        Print(" Sent"),
+        SetBackgroundColor(Reset),
+        SetForegroundColor(Reset),
+    )
+    .unwrap();
+
+    let current = Some(&tx_pane.current).filter(|current| !current.is_empty());
+    let lines: Vec<&String> = tx_pane.lines.iter().chain(current).collect();
+    let shown = &lines[lines.len().saturating_sub(line_rows)..];
+    for row in 0..line_rows {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, top + 1 + row as u16),
+            Clear(CurrentLine)
+        )
+        .unwrap();
+        if let Some(line) = shown.get(row) {
+            let line: String = line.chars().take(cols.into()).collect();
+            queue!(stdout, Print(line)).unwrap();
+        }
+    }
+    queue!(stdout, cursor::RestorePosition).unwrap();
+}